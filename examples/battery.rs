@@ -44,7 +44,10 @@ fn main() -> ! {
     loop {
         display.clear().expect("Unable to clear display");
         FONT.render_aligned(
-            format_args!("Voltage: {}V", battery.read()),
+            format_args!(
+                "Voltage: {}V",
+                battery.read().expect("Unable to read battery voltage")
+            ),
             Point::new(
                 display.bounding_box().center().x,
                 display.bounding_box().center().y,
@@ -4,7 +4,11 @@
 extern crate alloc;
 extern crate lilygo_epd47;
 
-use core::{format_args, time::Duration};
+use core::{
+    format_args,
+    ptr::{addr_of, addr_of_mut},
+    time::Duration,
+};
 
 use embedded_graphics::prelude::*;
 use embedded_graphics_core::{
@@ -26,6 +30,7 @@ use esp_hal::{
     system::Cpu,
 };
 use lilygo_epd47::{pin_config, Display, DrawMode};
+use log::warn;
 use u8g2_fonts::FontRenderer;
 
 static FONT: FontRenderer = FontRenderer::new::<u8g2_fonts::fonts::u8g2_font_spleen16x32_mr>();
@@ -36,13 +41,10 @@ esp_bootloader_esp_idf::esp_app_desc!();
 static mut CYCLE: u16 = 0;
 
 #[ram(unstable(rtc_fast))]
-static mut LAST_RECT: Rectangle = Rectangle {
-    top_left: Point { x: 0, y: 0 },
-    size: Size {
-        width: 0,
-        height: 0,
-    },
-};
+static mut SNAPSHOT: [u8; 4096] = [0; 4096];
+
+#[ram(unstable(rtc_fast))]
+static mut SNAPSHOT_LEN: usize = 0;
 
 #[main]
 fn main() -> ! {
@@ -70,48 +72,58 @@ fn main() -> ! {
     // turn screen on
     display.power_on();
     delay.delay_millis(20);
-    // clear
     let cycle = unsafe { CYCLE };
-    let last_rect = unsafe { LAST_RECT };
-
-    if cycle > 0 && cycle % 5 != 0 {
+    let snapshot = unsafe { &(*addr_of!(SNAPSHOT))[..SNAPSHOT_LEN] };
+
+    // restore what the panel shows from the snapshot taken before sleeping
+    let restored = cycle > 0 && cycle % 5 != 0 && display.restore(snapshot).is_ok();
+    let last_rect: Rectangle = display
+        .screen_bounding_box()
+        .map(Rectangle::from)
+        .unwrap_or_default();
+    if restored {
         display.fill_solid(&last_rect, Gray4::WHITE).unwrap();
         display.flush(DrawMode::WhiteOnBlack).unwrap();
     } else {
         display.clear().unwrap();
+        display.track_screen();
     }
     // write out reset and wake reason
-    let rect = FONT
-        .render_aligned(
-            format_args!(
-                "Reset Reason: {:?}\nWake reason: {:?}\nCycle: {}\nRect: ({}, {}, {}, {})",
-                reason,
-                wake_reason,
-                cycle,
-                last_rect.top_left.x,
-                last_rect.top_left.y,
-                last_rect.size.width,
-                last_rect.size.height,
-            ),
-            Point::new(
-                display.bounding_box().center().x,
-                display.bounding_box().center().y,
-            ),
-            u8g2_fonts::types::VerticalPosition::Baseline,
-            u8g2_fonts::types::HorizontalAlignment::Center,
-            u8g2_fonts::types::FontColor::WithBackground {
-                fg: Gray4::BLACK,
-                bg: Gray4::WHITE,
-            },
-            &mut display,
-        )
-        .unwrap();
+    FONT.render_aligned(
+        format_args!(
+            "Reset Reason: {:?}\nWake reason: {:?}\nCycle: {}\nRect: ({}, {}, {}, {})",
+            reason,
+            wake_reason,
+            cycle,
+            last_rect.top_left.x,
+            last_rect.top_left.y,
+            last_rect.size.width,
+            last_rect.size.height,
+        ),
+        Point::new(
+            display.bounding_box().center().x,
+            display.bounding_box().center().y,
+        ),
+        u8g2_fonts::types::VerticalPosition::Baseline,
+        u8g2_fonts::types::HorizontalAlignment::Center,
+        u8g2_fonts::types::FontColor::WithBackground {
+            fg: Gray4::BLACK,
+            bg: Gray4::WHITE,
+        },
+        &mut display,
+    )
+    .unwrap();
     display.flush(DrawMode::BlackOnWhite).unwrap();
     // turn screen off
     display.power_off();
     unsafe {
-        if let Some(rect) = rect {
-            LAST_RECT = rect;
+        match display.snapshot(&mut *addr_of_mut!(SNAPSHOT)) {
+            Ok(len) => SNAPSHOT_LEN = len,
+            Err(err) => {
+                // a previous snapshot would restore an outdated image
+                warn!("snapshot failed: {}", err);
+                SNAPSHOT_LEN = 0;
+            }
         }
         CYCLE += 1;
     }
@@ -0,0 +1,68 @@
+#![no_std]
+#![no_main]
+
+extern crate lilygo_epd47;
+
+use embedded_graphics::{prelude::*, primitives::Rectangle};
+use embedded_graphics_core::pixelcolor::Rgb888;
+#[allow(unused_imports)]
+use esp_backtrace as _;
+use esp_hal::{delay::Delay, main};
+use lilygo_epd47::{pin_config, Display, Dithering, DitheringDrawTarget, DrawMode};
+
+esp_bootloader_esp_idf::esp_app_desc!();
+
+#[main]
+fn main() -> ! {
+    esp_println::logger::init_logger_from_env();
+
+    let peripherals = esp_hal::init(esp_hal::Config::default());
+
+    // Create PSRAM allocator
+    esp_alloc::psram_allocator!(peripherals.PSRAM, esp_hal::psram);
+
+    let mut display = Display::new(
+        pin_config!(peripherals),
+        peripherals.DMA_CH0,
+        peripherals.LCD_CAM,
+        peripherals.RMT,
+    )
+    .expect("Failed to initialize display");
+
+    let delay = Delay::new();
+    display.power_on();
+    delay.delay_millis(10);
+    display.clear().expect("Unable to clear display");
+
+    // One horizontal band per algorithm, each showing a color gradient
+    let methods = [
+        Dithering::Bayer,
+        Dithering::FloydSteinberg,
+        Dithering::Atkinson,
+    ];
+    let size = display.bounding_box().size;
+    let height = size.height / methods.len() as u32;
+    for (i, method) in methods.into_iter().enumerate() {
+        let area = Rectangle::new(
+            Point::new(0, height as i32 * i as i32),
+            Size::new(size.width, height),
+        );
+        let colors = area.points().map(|p| {
+            let x = (p.x * 255 / size.width as i32) as u8;
+            Rgb888::new(x, 255 - x, x / 2)
+        });
+        DitheringDrawTarget::new(&mut display, method)
+            .fill_contiguous(&area, colors)
+            .expect("Unable to draw on display");
+    }
+
+    display
+        .flush(DrawMode::BlackOnWhite)
+        .expect("Unable to flush on display");
+
+    display.power_off();
+
+    loop {
+        delay.delay_millis(1000);
+    }
+}
@@ -7,7 +7,8 @@ extern crate lilygo_epd47;
 
 use esp_backtrace as _;
 use esp_hal::{delay::Delay, main};
-use lilygo_epd47::{pin_config, Display};
+use lilygo_epd47::{pin_config, repair::RepairStatus, Display, Repair, RepairConfig};
+use log::info;
 
 esp_bootloader_esp_idf::esp_app_desc!();
 
@@ -31,7 +32,19 @@ fn main() -> ! {
     let delay = Delay::new();
     display.power_on();
     delay.delay_millis(10);
-    display.repair(delay).expect("Unable to repair display");
+    // `display.repair(RepairConfig::default(), &mut delay)` runs the same routine
+    // blocking, driving it step by step allows reporting progress and cancelling
+    let mut repair = Repair::new(RepairConfig::default());
+    loop {
+        match repair.step(&mut display).expect("Unable to repair display") {
+            RepairStatus::Wait(ms) => {
+                let (completed, total) = repair.progress();
+                info!("repair: {}/{}", completed, total);
+                delay.delay_millis(ms);
+            }
+            RepairStatus::Done | RepairStatus::Cancelled => break,
+        }
+    }
     display.power_off();
 
     loop {}
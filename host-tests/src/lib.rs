@@ -0,0 +1,85 @@
+//! Host tests of the hardware independent parts of `lilygo-epd47`.
+//!
+//! The driver depends on `esp-hal` and can't be built for the host, so the
+//! modules which don't access the peripherals are compiled from the sources
+//! of the driver and tested here. Run `cargo test` in this directory.
+
+// the crate internal items of the driver are only used by the tests here
+#![cfg_attr(not(test), allow(dead_code))]
+
+// the logging macros are used by the other modules
+#[macro_use]
+#[path = "../../src/fmt.rs"]
+mod fmt;
+
+#[path = "../../src/blit.rs"]
+pub mod blit;
+#[path = "../../src/codec.rs"]
+pub mod codec;
+#[path = "../../src/depth.rs"]
+pub mod depth;
+#[path = "../../src/dirty_rows.rs"]
+pub mod dirty_rows;
+#[path = "../../src/dither.rs"]
+pub mod dither;
+#[path = "../../src/framebuffer.rs"]
+pub mod framebuffer;
+#[path = "../../src/image.rs"]
+pub mod image;
+#[path = "../../src/panel_config.rs"]
+pub mod panel_config;
+#[path = "../../src/repair.rs"]
+pub mod repair;
+#[path = "../../src/screenshot.rs"]
+pub mod screenshot;
+#[path = "../../src/stream.rs"]
+pub mod stream;
+#[path = "../../src/timings.rs"]
+pub mod timings;
+#[path = "../../src/tone.rs"]
+pub mod tone;
+#[path = "../../src/tps65185.rs"]
+pub mod tps65185;
+#[path = "../../src/waveform.rs"]
+pub mod waveform;
+
+pub use depth::{Bpp1, Bpp2, Bpp4};
+
+#[cfg(test)]
+mod tests;
+
+/// Errors of the shared driver sources, mirrors `lilygo_epd47::Error`
+/// without the pass-through errors of `esp-hal`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Error {
+    /// Provided pixel coordinates exceed the display boundary.
+    OutOfBounds,
+    /// Provided color exceeds the allowed range of 0x0 - 0x0F
+    InvalidColor,
+    /// Pass-through
+    I2c(embedded_hal::i2c::ErrorKind),
+    /// The PMIC did not report all rails as good in time.
+    PowerGood,
+    /// Provided VCOM voltage exceeds the range supported by the PMIC.
+    InvalidVcom,
+    /// The device did not respond in time.
+    Timeout,
+    /// The provided configuration blob is corrupted or incompatible.
+    InvalidConfig,
+    /// The provided output buffer is too small.
+    BufferTooSmall,
+    /// The provided encoded image data or snapshot is corrupted.
+    InvalidEncoding,
+    /// The provided buffers differ in size.
+    SizeMismatch,
+    /// The provided timings are out of range.
+    InvalidTimings,
+    /// The provided tone curve is not monotonic.
+    InvalidToneCurve,
+    /// Pass-through
+    Io(embedded_io::ErrorKind),
+    /// The format of the provided image is not supported.
+    UnsupportedFormat,
+}
+
+type Result<T> = core::result::Result<T, Error>;
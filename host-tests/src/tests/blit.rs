@@ -0,0 +1,225 @@
+use proptest::prelude::*;
+
+use super::{framebuffer::pixel, random};
+use crate::{
+    blit::{blit, Mask, RasterOp},
+    depth::{Bpp4, PixelDepth},
+    framebuffer::{write_pixel, Rectangle, BOUNDING_BOX},
+    Error,
+};
+
+/// Source image of a blit with the mask bits, every row starts `stride`
+/// bytes after the previous one.
+#[derive(Debug)]
+struct Source {
+    area: Rectangle,
+    data: Vec<u8>,
+    stride: usize,
+    mask: Option<(Vec<u8>, usize)>,
+}
+
+impl Source {
+    fn mask(&self) -> Option<Mask<'_>> {
+        self.mask.as_ref().map(|(data, stride)| Mask {
+            data,
+            stride: *stride,
+        })
+    }
+}
+
+/// Combines every pixel of the source on its own.
+fn reference(buffer: &mut [u8], clip: Option<Rectangle>, source: &Source, op: RasterOp) {
+    let area = source.area;
+    for j in 0..area.height as usize {
+        for i in 0..area.width as usize {
+            let (x, y) = (area.x as usize + i, area.y as usize + j);
+            if !clip.is_some_and(|clip| clip.contains(x as u16, y as u16)) {
+                continue;
+            }
+            if let Some((mask, stride)) = &source.mask {
+                if mask[j * stride + i / 8] >> (i % 8) & 1 == 0 {
+                    continue;
+                }
+            }
+            let src = source.data[j * source.stride + i / 2] >> (4 * (i % 2)) & 0x0F;
+            let target = pixel::<Bpp4>(buffer, x as u16, y as u16);
+            let value = match op {
+                RasterOp::Copy => src,
+                RasterOp::And => target & src,
+                RasterOp::Or => target | src,
+                RasterOp::Xor => target ^ src,
+                RasterOp::Invert => !src & 0x0F,
+                RasterOp::Transparent(key) if src == key => continue,
+                RasterOp::Transparent(_) => src,
+            };
+            write_pixel::<Bpp4>(buffer, x, y, value);
+        }
+    }
+}
+
+fn op() -> impl Strategy<Value = RasterOp> {
+    prop_oneof![
+        Just(RasterOp::Copy),
+        Just(RasterOp::And),
+        Just(RasterOp::Or),
+        Just(RasterOp::Xor),
+        Just(RasterOp::Invert),
+        (0u8..16).prop_map(RasterOp::Transparent),
+    ]
+}
+
+/// Offsets of the areas and clip rectangles, at the origin and near the right
+/// and bottom edges of the screen.
+fn origin() -> impl Strategy<Value = (u16, u16)> {
+    prop_oneof![Just((0, 0)), Just((901, 500)), Just((940, 529))]
+}
+
+fn source() -> impl Strategy<Value = Source> {
+    (
+        origin(),
+        (0u16..40, 0u16..20, 0u16..48, 0u16..12),
+        (0usize..3, 0usize..3),
+        any::<bool>(),
+        any::<u32>(),
+    )
+        .prop_map(
+            |((x0, y0), (x, y, width, height), (pad, mask_pad), masked, seed)| {
+                let rows = height as usize;
+                let stride = (width as usize).div_ceil(2) + pad;
+                let mask_stride = (width as usize).div_ceil(8) + mask_pad;
+                Source {
+                    area: Rectangle {
+                        x: x0 + x,
+                        y: y0 + y,
+                        width,
+                        height,
+                    },
+                    // the last row doesn't need padding
+                    data: random::bytes((stride * rows).saturating_sub(pad), seed),
+                    stride,
+                    mask: masked.then(|| {
+                        let len = (mask_stride * rows).saturating_sub(mask_pad);
+                        (random::bytes(len, seed.wrapping_add(1)), mask_stride)
+                    }),
+                }
+            },
+        )
+}
+
+fn clip() -> impl Strategy<Value = Option<Rectangle>> {
+    prop_oneof![
+        1 => Just(Some(BOUNDING_BOX)),
+        1 => Just(None),
+        4 => (origin(), 0u16..50, 0u16..25, 0u16..60, 0u16..25).prop_map(
+            |((x0, y0), x, y, width, height)| {
+                Rectangle {
+                    x: x0 + x,
+                    y: y0 + y,
+                    width,
+                    height,
+                }
+                .intersection(&BOUNDING_BOX)
+            }
+        ),
+    ]
+}
+
+proptest! {
+    #[test]
+    fn blit_matches_reference(
+        source in source(),
+        clip in clip(),
+        op in op(),
+        seed: u32,
+    ) {
+        let original = random::bytes(Bpp4::FRAMEBUFFER_SIZE, seed);
+        let mut expected = original.clone();
+        reference(&mut expected, clip, &source, op);
+        let mut buffer = original.clone();
+        let changed = blit(
+            &mut buffer,
+            clip,
+            source.area,
+            &source.data,
+            source.stride,
+            op,
+            source.mask(),
+        );
+        prop_assert_eq!(changed, Ok(clip.and_then(|clip| clip.intersection(&source.area))));
+        prop_assert!(buffer == expected);
+    }
+}
+
+#[test]
+fn rejects_short_data() {
+    let area = Rectangle {
+        x: 1,
+        y: 2,
+        width: 5,
+        height: 3,
+    };
+    let data = [0x5A; 9];
+    let mask = [0xFF; 3];
+    let original = random::bytes(Bpp4::FRAMEBUFFER_SIZE, 1);
+    let mut buffer = original.clone();
+    let clip = Some(BOUNDING_BOX);
+    let op = RasterOp::Copy;
+    let mask_of = |data, stride| Some(Mask { data, stride });
+    assert_eq!(
+        blit(&mut buffer, clip, area, &data, 3, op, mask_of(&mask, 1)),
+        Ok(Some(area))
+    );
+
+    let mut buffer = original.clone();
+    for (data, stride, mask) in [
+        // stride shorter than a row
+        (&data[..], 2, None),
+        (&data[..8], 3, None),
+        (&data[..], 3, mask_of(&mask[..2], 1)),
+        (&data[..], 3, mask_of(&mask, 0)),
+    ] {
+        assert_eq!(
+            blit(&mut buffer, clip, area, data, stride, op, mask),
+            Err(Error::SizeMismatch)
+        );
+    }
+    assert!(buffer == original);
+}
+
+#[test]
+fn rejects_invalid_key() {
+    let area = Rectangle {
+        x: 0,
+        y: 0,
+        width: 2,
+        height: 1,
+    };
+    let mut buffer = vec![0; Bpp4::FRAMEBUFFER_SIZE];
+    let op = RasterOp::Transparent(0x10);
+    assert_eq!(
+        blit(&mut buffer, Some(BOUNDING_BOX), area, &[0x12], 1, op, None),
+        Err(Error::InvalidColor)
+    );
+    assert!(buffer.iter().all(|&byte| byte == 0));
+}
+
+#[test]
+fn empty_area_needs_no_data() {
+    let area = Rectangle {
+        x: 3,
+        y: 4,
+        width: 7,
+        height: 0,
+    };
+    let mut buffer = vec![0; Bpp4::FRAMEBUFFER_SIZE];
+    let result = blit(
+        &mut buffer,
+        Some(BOUNDING_BOX),
+        area,
+        &[],
+        0,
+        RasterOp::Copy,
+        None,
+    );
+    assert_eq!(result, Ok(None));
+}
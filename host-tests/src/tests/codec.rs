@@ -0,0 +1,281 @@
+use super::random::Xorshift;
+use crate::{
+    codec::{apply_delta, decode, encode, encode_delta, Decoder},
+    Error,
+};
+
+/// Size of a 4bpp framebuffer.
+const FRAMEBUFFER_SIZE: usize = 960 / 2 * 540;
+
+/// Pseudo random bytes without three equal bytes in a row, i.e. literals only.
+fn noise(len: usize, seed: u32) -> Vec<u8> {
+    let mut data: Vec<u8> = Vec::with_capacity(len);
+    for byte in Xorshift::new(seed) {
+        if data.len() == len {
+            break;
+        }
+        if !data.ends_with(&[byte, byte]) {
+            data.push(byte);
+        }
+    }
+    data
+}
+
+/// Runs of the given lengths, separated by two literal bytes.
+fn runs(lengths: &[usize]) -> Vec<u8> {
+    let mut data = Vec::new();
+    for (i, &len) in lengths.iter().enumerate() {
+        data.extend(std::iter::repeat_n(i as u8, len));
+        data.extend([0xA5, 0x5A]);
+    }
+    data
+}
+
+fn encoded(data: &[u8]) -> Vec<u8> {
+    let mut out = vec![0; data.len() * 2 + 16];
+    let len = encode(data, &mut out).unwrap();
+    out.truncate(len);
+    out
+}
+
+fn decoded(data: &[u8], len: usize) -> Result<Vec<u8>, Error> {
+    let mut out = vec![0; len];
+    decode(data, &mut out)?;
+    Ok(out)
+}
+
+fn samples() -> Vec<Vec<u8>> {
+    vec![
+        Vec::new(),
+        vec![0x12],
+        vec![0x34; 2],
+        vec![0x56; 3],
+        vec![0xFF; FRAMEBUFFER_SIZE],
+        runs(&[
+            1, 2, 3, 4, 63, 64, 65, 66, 127, 128, 129, 8191, 8192, 8193, 20000,
+        ]),
+        noise(64, 1),
+        noise(65, 2),
+        noise(5000, 3),
+        [noise(300, 4), vec![0; 300], noise(3, 5), vec![0xFF; 3]].concat(),
+    ]
+}
+
+#[test]
+fn encodes_tokens() {
+    let cases: &[(&[u8], &[u8])] = &[
+        (&[], &[]),
+        (&[7], &[0x01, 7]),
+        (&[7, 7], &[0x03, 7, 7]),
+        // runs start at MIN_RUN bytes
+        (&[7, 7, 7], &[0x04, 7]),
+        (&[1, 2, 3, 3, 3], &[0x03, 1, 2, 0x04, 3]),
+        (&[3, 3, 3, 1, 2], &[0x04, 3, 0x03, 1, 2]),
+        (&[1, 1, 2, 2, 2, 2, 1], &[0x03, 1, 1, 0x06, 2, 0x01, 1]),
+    ];
+    for &(data, expected) in cases {
+        assert_eq!(encoded(data), expected, "{data:?}");
+    }
+}
+
+#[test]
+fn encodes_long_headers() {
+    // the header of 64 bytes still fits into 7 bits
+    assert_eq!(encoded(&[9; 64]), [0x7E, 9]);
+    assert_eq!(encoded(&[9; 65]), [0x80, 0x01, 9]);
+    let literal = noise(65, 6);
+    assert_eq!(encoded(&literal)[..2], [0x81, 0x01]);
+    assert_eq!(encoded(&literal).len(), 2 + 65);
+    // 2 * (129600 - 1) needs 3 bytes
+    assert_eq!(
+        encoded(&[0xFF; FRAMEBUFFER_SIZE / 2]),
+        [0xFE, 0xE8, 0x0F, 0xFF]
+    );
+}
+
+#[test]
+fn round_trip() {
+    for data in samples() {
+        let encoded = encoded(&data);
+        assert_eq!(decoded(&encoded, data.len()), Ok(data));
+    }
+}
+
+#[test]
+fn round_trip_delta() {
+    for data in samples() {
+        let previous = noise(data.len(), 7);
+        let mut out = vec![0; data.len() * 2 + 16];
+        let len = encode_delta(&data, &previous, &mut out).unwrap();
+        let mut frame = previous.clone();
+        apply_delta(&out[..len], &mut frame).unwrap();
+        assert_eq!(frame, data);
+    }
+}
+
+#[test]
+fn delta_of_equal_frames_is_a_run() {
+    let frame = noise(FRAMEBUFFER_SIZE, 8);
+    let mut out = [0; 8];
+    let len = encode_delta(&frame, &frame, &mut out).unwrap();
+    assert_eq!(&out[..len], [0xFE, 0xD1, 0x1F, 0x00]);
+}
+
+#[test]
+fn delta_rejects_size_mismatch() {
+    let mut out = [0; 16];
+    assert_eq!(
+        encode_delta(&[0; 4], &[0; 5], &mut out),
+        Err(Error::SizeMismatch)
+    );
+}
+
+#[test]
+fn decoder_reads_chunks() {
+    for data in samples() {
+        let encoded = encoded(&data);
+        for chunk in (1..=70).chain([129, 1000, 65536]) {
+            let mut decoder = Decoder::new(&encoded);
+            let mut out = vec![0; data.len()];
+            for target in out.chunks_mut(chunk) {
+                decoder.read(target).unwrap();
+            }
+            assert!(decoder.is_finished(), "chunk size {chunk}");
+            assert_eq!(out, data, "chunk size {chunk}");
+        }
+    }
+}
+
+#[test]
+fn decoder_skips_chunks() {
+    for data in samples() {
+        let encoded = encoded(&data);
+        for chunk in [1, 2, 3, 5, 64, 65, 1000] {
+            let mut decoder = Decoder::new(&encoded);
+            let mut pos = 0;
+            // alternate between reading and skipping chunks of varying size
+            for (i, len) in (chunk..).step_by(7).enumerate() {
+                let len = len.min(data.len() - pos);
+                if i % 2 == 0 {
+                    let mut out = vec![0; len];
+                    decoder.read(&mut out).unwrap();
+                    assert_eq!(out, data[pos..pos + len], "chunk size {chunk}");
+                } else {
+                    decoder.skip(len).unwrap();
+                }
+                pos += len;
+                if pos == data.len() {
+                    break;
+                }
+            }
+            assert!(decoder.is_finished(), "chunk size {chunk}");
+        }
+    }
+}
+
+#[test]
+fn decoder_of_empty_stream() {
+    let mut decoder = Decoder::new(&[]);
+    assert!(decoder.is_finished());
+    assert_eq!(decoder.read(&mut []), Ok(()));
+    assert_eq!(decoder.skip(0), Ok(()));
+    assert_eq!(decoder.read(&mut [0]), Err(Error::InvalidEncoding));
+    assert_eq!(decoder.skip(1), Err(Error::InvalidEncoding));
+}
+
+#[test]
+fn encode_reports_small_buffer() {
+    for data in samples() {
+        let needed = encoded(&data).len();
+        for len in 0..needed.min(100) {
+            let mut out = vec![0; len];
+            assert_eq!(encode(&data, &mut out), Err(Error::BufferTooSmall));
+        }
+        let mut out = vec![0; needed];
+        assert_eq!(encode(&data, &mut out), Ok(needed));
+        if needed > 0 {
+            // XOR with zeros keeps the data
+            let previous = vec![0; data.len()];
+            let mut out = vec![0; needed - 1];
+            assert_eq!(
+                encode_delta(&data, &previous, &mut out),
+                Err(Error::BufferTooSmall)
+            );
+        }
+    }
+}
+
+#[test]
+fn rejects_truncated_stream() {
+    for data in samples() {
+        let encoded = encoded(&data);
+        for len in 0..encoded.len() {
+            let truncated = &encoded[..len];
+            assert_eq!(decoded(truncated, data.len()), Err(Error::InvalidEncoding));
+            let mut frame = vec![0; data.len()];
+            assert_eq!(
+                apply_delta(truncated, &mut frame),
+                Err(Error::InvalidEncoding)
+            );
+            let mut out = vec![0; data.len()];
+            assert_eq!(
+                Decoder::new(truncated).read(&mut out),
+                Err(Error::InvalidEncoding)
+            );
+        }
+    }
+}
+
+#[test]
+fn rejects_oversized_stream() {
+    for data in samples() {
+        let mut encoded = encoded(&data);
+        if !data.is_empty() {
+            // the stream doesn't fit into a shorter output
+            assert_eq!(
+                decoded(&encoded, data.len() - 1),
+                Err(Error::InvalidEncoding)
+            );
+        }
+        encoded.extend([0x00, 0x42]);
+        assert_eq!(decoded(&encoded, data.len()), Err(Error::InvalidEncoding));
+        let mut frame = vec![0; data.len()];
+        assert_eq!(
+            apply_delta(&encoded, &mut frame),
+            Err(Error::InvalidEncoding)
+        );
+        let mut decoder = Decoder::new(&encoded);
+        decoder.skip(data.len()).unwrap();
+        assert!(!decoder.is_finished());
+    }
+}
+
+#[test]
+fn rejects_token_exceeding_output() {
+    // a run of 0x7FFF_FFFF + 1 bytes
+    let data = [0xFE, 0xFF, 0xFF, 0xFF, 0x0F, 0x00];
+    assert_eq!(decoded(&data, 16), Err(Error::InvalidEncoding));
+    let mut decoder = Decoder::new(&data);
+    assert_eq!(decoder.skip(16), Ok(()));
+    assert!(!decoder.is_finished());
+}
+
+#[test]
+fn varint_is_limited_to_five_bytes() {
+    // a run of a single byte with a header padded to five bytes
+    let data = [0x80, 0x80, 0x80, 0x80, 0x00, 0x42];
+    assert_eq!(decoded(&data, 1), Ok(vec![0x42]));
+    let mut out = [0];
+    assert_eq!(Decoder::new(&data).read(&mut out), Ok(()));
+    assert_eq!(out, [0x42]);
+
+    let data = [0x80, 0x80, 0x80, 0x80, 0x80, 0x00, 0x42];
+    assert_eq!(decoded(&data, 1), Err(Error::InvalidEncoding));
+    let mut frame = [0];
+    assert_eq!(apply_delta(&data, &mut frame), Err(Error::InvalidEncoding));
+    assert_eq!(
+        Decoder::new(&data).read(&mut out),
+        Err(Error::InvalidEncoding)
+    );
+    assert_eq!(Decoder::new(&data).skip(1), Err(Error::InvalidEncoding));
+}
@@ -0,0 +1,129 @@
+use std::collections::BTreeSet;
+
+use proptest::prelude::*;
+
+use crate::dirty_rows::DirtyRows;
+
+/// Number of rows of the screen.
+const ROWS: u16 = 540;
+
+fn rows(dirty: &DirtyRows) -> Vec<u16> {
+    dirty.iter().collect()
+}
+
+fn with_range(range: std::ops::Range<u16>) -> DirtyRows {
+    let mut dirty = DirtyRows::new();
+    dirty.insert_range(range);
+    dirty
+}
+
+#[test]
+fn insert_range_within_word() {
+    let dirty = with_range(0..32);
+    assert_eq!(rows(&dirty), (0..32).collect::<Vec<_>>());
+    assert!(!dirty.contains(32));
+    assert_eq!(rows(&with_range(3..7)), [3, 4, 5, 6]);
+    assert_eq!(rows(&with_range(32..64)), (32..64).collect::<Vec<_>>());
+}
+
+#[test]
+fn insert_range_across_words() {
+    assert_eq!(rows(&with_range(31..33)), [31, 32]);
+    assert_eq!(rows(&with_range(30..66)), (30..66).collect::<Vec<_>>());
+}
+
+#[test]
+fn insert_range_of_screen() {
+    let dirty = with_range(0..ROWS);
+    assert_eq!(rows(&dirty), (0..ROWS).collect::<Vec<_>>());
+    let mut all = DirtyRows::new();
+    all.insert_all();
+    assert_eq!(all, dirty);
+}
+
+#[test]
+// reversed ranges are empty as well
+#[allow(clippy::reversed_empty_ranges)]
+fn insert_empty_range() {
+    for range in [0..0, 31..31, 32..32, 539..539, 10..3] {
+        assert!(with_range(range.clone()).is_empty(), "{range:?}");
+    }
+}
+
+#[test]
+fn ignores_rows_outside_screen() {
+    let mut dirty = DirtyRows::new();
+    dirty.insert(ROWS);
+    dirty.insert(u16::MAX);
+    dirty.insert_range(ROWS..ROWS + 100);
+    dirty.insert_range(600..u16::MAX);
+    assert!(dirty.is_empty());
+    assert!(!dirty.contains(ROWS));
+    assert!(!dirty.contains(u16::MAX));
+
+    // ranges are cut at the last row
+    dirty.insert_range(530..600);
+    assert_eq!(rows(&dirty), (530..ROWS).collect::<Vec<_>>());
+    dirty.insert_range(0..u16::MAX);
+    assert_eq!(rows(&dirty).len(), ROWS as usize);
+}
+
+#[test]
+fn iter_is_ascending() {
+    let mut dirty = DirtyRows::new();
+    for row in [539, 0, 64, 31, 32, 63, 100, 1] {
+        dirty.insert(row);
+    }
+    assert_eq!(rows(&dirty), [0, 1, 31, 32, 63, 64, 100, 539]);
+    assert_eq!(rows(&DirtyRows::new()), [] as [u16; 0]);
+}
+
+#[test]
+fn union_adds_rows() {
+    let mut dirty = with_range(0..10);
+    dirty.union(&with_range(5..40));
+    dirty.union(&with_range(500..ROWS));
+    dirty.union(&DirtyRows::new());
+    let expected: Vec<u16> = (0..40).chain(500..ROWS).collect();
+    assert_eq!(rows(&dirty), expected);
+}
+
+#[test]
+fn clear_removes_rows() {
+    let mut dirty = with_range(0..ROWS);
+    dirty.clear();
+    assert!(dirty.is_empty());
+    assert_eq!(dirty, DirtyRows::default());
+}
+
+proptest! {
+    #[test]
+    fn matches_set_of_rows(
+        ranges in prop::collection::vec((0u16..600, 0u16..600), 0..8),
+        single in prop::collection::vec(0u16..600, 0..8),
+        other in prop::collection::vec((0u16..600, 0u16..600), 0..4),
+    ) {
+        let mut dirty = DirtyRows::new();
+        let mut expected = BTreeSet::new();
+        for &(start, end) in &ranges {
+            dirty.insert_range(start..end);
+            expected.extend((start..end).filter(|&row| row < ROWS));
+        }
+        for &row in &single {
+            dirty.insert(row);
+            expected.extend((row < ROWS).then_some(row));
+        }
+        let mut union = DirtyRows::new();
+        for &(start, end) in &other {
+            union.insert_range(start..end);
+            expected.extend((start..end).filter(|&row| row < ROWS));
+        }
+        dirty.union(&union);
+
+        prop_assert_eq!(rows(&dirty), expected.iter().copied().collect::<Vec<_>>());
+        prop_assert_eq!(dirty.is_empty(), expected.is_empty());
+        for row in 0..600 {
+            prop_assert_eq!(dirty.contains(row), expected.contains(&row));
+        }
+    }
+}
@@ -0,0 +1,242 @@
+use std::{env, fs};
+
+use crate::{
+    depth::{Bpp1, Bpp2, Bpp4, PixelDepth},
+    dither::{Ditherer, Dithering},
+    tone::ToneCurve,
+};
+
+const ALGORITHMS: [Dithering; 3] = [
+    Dithering::Bayer,
+    Dithering::FloydSteinberg,
+    Dithering::Atkinson,
+];
+
+/// Curve with dark levels which are closer together than the light ones.
+fn uneven_curve() -> ToneCurve {
+    ToneCurve::new([
+        0, 6, 12, 19, 27, 37, 49, 63, 79, 97, 117, 139, 163, 190, 221, 255,
+    ])
+    .unwrap()
+}
+
+fn curves() -> [ToneCurve; 2] {
+    [uneven_curve(), ToneCurve::LINEAR]
+}
+
+/// Photo of the display (`_docs/hello-world.jpg`) scaled down to 64x32.
+const REFERENCE: &[u8] = include_bytes!("images/hello-world.pgm");
+
+/// Dithers an image of the given size row by row and returns the lightness
+/// of the quantised pixels.
+fn dither<D: PixelDepth>(
+    dithering: Dithering,
+    curve: &ToneCurve,
+    width: u16,
+    height: u16,
+    luma: impl Fn(u16, u16) -> u8,
+) -> Vec<Vec<i32>> {
+    let mut ditherer = Ditherer::new(dithering);
+    (0..height)
+        .map(|y| {
+            (0..width)
+                .map(|x| {
+                    let value = ditherer.quantise::<D>(x, y, luma(x, y), curve);
+                    assert!(value <= D::MAX_COLOR);
+                    curve.lightness::<D>(value)
+                })
+                .collect()
+        })
+        .collect()
+}
+
+/// Maximum difference between the mean lightness of the output and the input.
+fn tolerance<D: PixelDepth>(dithering: Dithering) -> f64 {
+    match dithering {
+        Dithering::Bayer | Dithering::FloydSteinberg => 4.0,
+        // Atkinson drops a quarter of the error, so errors smaller than a
+        // fraction of the gap between two levels are never passed on
+        Dithering::Atkinson => match D::BITS {
+            4 => 8.0,
+            2 => 16.0,
+            _ => 32.0,
+        },
+    }
+}
+
+fn mean(values: impl IntoIterator<Item = i32>) -> f64 {
+    let (sum, count) = values
+        .into_iter()
+        .fold((0, 0), |(sum, count), value| (sum + value, count + 1));
+    sum as f64 / count as f64
+}
+
+fn check_flat<D: PixelDepth>() {
+    for dithering in ALGORITHMS {
+        for curve in curves() {
+            for luma in [96, 128, 160] {
+                let target = curve.target(luma as i32) as f64;
+                let pixels = dither::<D>(dithering, &curve, 128, 64, |_, _| luma);
+                let mean = mean(pixels.into_iter().flatten());
+                assert!(
+                    (mean - target).abs() <= tolerance::<D>(dithering),
+                    "{dithering:?} {curve:?} luma {luma}: {mean} instead of {target}"
+                );
+            }
+        }
+    }
+}
+
+fn check_gradient<D: PixelDepth>() {
+    const WIDTH: u16 = 960;
+    const BAND: usize = 64;
+    for dithering in ALGORITHMS {
+        for curve in curves() {
+            let luma = |x: u16, _| (x as u32 * 255 / (WIDTH as u32 - 1)) as u8;
+            let pixels = dither::<D>(dithering, &curve, WIDTH, 32, luma);
+            let mut previous = f64::MIN;
+            for band in 0..WIDTH as usize / BAND {
+                let columns = band * BAND..(band + 1) * BAND;
+                let mean = mean(pixels.iter().flat_map(|row| row[columns.clone()].to_vec()));
+                let target = mean_target(&curve, columns, luma);
+                assert!(
+                    (mean - target).abs() <= tolerance::<D>(dithering),
+                    "{dithering:?} {curve:?} band {band}: {mean} instead of {target}"
+                );
+                assert!(mean >= previous, "{dithering:?} {curve:?} band {band}");
+                previous = mean;
+            }
+        }
+    }
+}
+
+fn mean_target(
+    curve: &ToneCurve,
+    columns: std::ops::Range<usize>,
+    luma: impl Fn(u16, u16) -> u8,
+) -> f64 {
+    mean(columns.map(|x| curve.target(luma(x as u16, 0) as i32)))
+}
+
+fn check_black_and_white<D: PixelDepth>() {
+    for dithering in ALGORITHMS {
+        for curve in curves() {
+            for (luma, value) in [(0, 0), (255, D::MAX_COLOR)] {
+                let mut ditherer = Ditherer::new(dithering);
+                for y in 0..16 {
+                    for x in 0..64 {
+                        assert_eq!(
+                            ditherer.quantise::<D>(x, y, luma, &curve),
+                            value,
+                            "{dithering:?} {curve:?} at {x}, {y}"
+                        );
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Splits a binary PGM image with the header written by [pgm] into its size
+/// and pixels.
+fn read_pgm(data: &[u8]) -> (u16, u16, &[u8]) {
+    let mut fields = data.splitn(5, |byte| byte.is_ascii_whitespace());
+    assert_eq!(fields.next(), Some(&b"P5"[..]));
+    let mut number = || -> u16 {
+        let field = fields.next().unwrap();
+        std::str::from_utf8(field).unwrap().parse().unwrap()
+    };
+    let (width, height) = (number(), number());
+    assert_eq!(number(), 255);
+    let pixels = fields.next().unwrap();
+    assert_eq!(pixels.len(), width as usize * height as usize);
+    (width, height, pixels)
+}
+
+fn pgm(width: u16, height: u16, pixels: &[u8]) -> Vec<u8> {
+    let mut data = format!("P5\n{width} {height}\n255\n").into_bytes();
+    data.extend_from_slice(pixels);
+    data
+}
+
+/// Dithers the reference image and compares the result with the golden
+/// image `images/hello-world-<bits>bpp-<name>.pgm`. Set `UPDATE_GOLDEN` to
+/// write the golden images instead, after checking the changes visually.
+fn check_reference<D: PixelDepth>(dithering: Dithering, name: &str) {
+    let (width, height, luma) = read_pgm(REFERENCE);
+    let mut ditherer = Ditherer::new(dithering);
+    let mut pixels = Vec::new();
+    for y in 0..height {
+        for x in 0..width {
+            let luma = luma[y as usize * width as usize + x as usize];
+            let value = ditherer.quantise::<D>(x, y, luma, &ToneCurve::LINEAR);
+            pixels.push(value * (0xFF / D::MAX_COLOR));
+        }
+    }
+    let output = pgm(width, height, &pixels);
+
+    let path = format!(
+        "{}/src/tests/images/hello-world-{}bpp-{name}.pgm",
+        env!("CARGO_MANIFEST_DIR"),
+        D::BITS
+    );
+    if env::var_os("UPDATE_GOLDEN").is_some() {
+        fs::write(&path, output).unwrap();
+        return;
+    }
+    let golden = fs::read(&path).unwrap();
+    assert!(
+        output == golden,
+        "{dithering:?} {}bpp differs from {path}",
+        D::BITS
+    );
+}
+
+#[test]
+fn reference_image_matches_golden() {
+    let names = ["bayer", "floyd-steinberg", "atkinson"];
+    for (dithering, name) in ALGORITHMS.into_iter().zip(names) {
+        check_reference::<Bpp1>(dithering, name);
+        check_reference::<Bpp4>(dithering, name);
+    }
+}
+
+#[test]
+fn flat_mid_gray_keeps_average() {
+    check_flat::<Bpp4>();
+    check_flat::<Bpp2>();
+    check_flat::<Bpp1>();
+}
+
+#[test]
+fn gradient_follows_input() {
+    check_gradient::<Bpp4>();
+    check_gradient::<Bpp2>();
+    check_gradient::<Bpp1>();
+}
+
+#[test]
+fn black_and_white_are_exact() {
+    check_black_and_white::<Bpp4>();
+    check_black_and_white::<Bpp2>();
+    check_black_and_white::<Bpp1>();
+}
+
+#[test]
+fn levels_of_the_curve_are_exact() {
+    // gray values which hit a level exactly need no dithering
+    let curve = ToneCurve::LINEAR;
+    for dithering in ALGORITHMS {
+        for level in 0..16u8 {
+            let pixels = dither::<Bpp4>(dithering, &curve, 32, 8, |_, _| level * 17);
+            let expected = curve.lightness::<Bpp4>(level);
+            assert!(
+                pixels
+                    .iter()
+                    .flatten()
+                    .all(|&lightness| lightness == expected),
+                "{dithering:?} level {level}"
+            );
+        }
+    }
+}
@@ -0,0 +1,245 @@
+use proptest::prelude::*;
+
+use crate::{
+    depth::{Bpp1, Bpp2, Bpp4, PixelDepth},
+    framebuffer::{
+        fill_rect,
+        screen_area,
+        screen_point,
+        set_pixel,
+        Rectangle,
+        BOUNDING_BOX,
+        HEIGHT,
+        WIDTH,
+    },
+    Error,
+};
+
+/// Coordinates and sizes around the screen, with some at the end of the
+/// `u16` range to catch overflows.
+fn coordinate() -> impl Strategy<Value = u16> {
+    prop_oneof![4 => 0u16..1100, 1 => any::<u16>()]
+}
+
+fn rectangle() -> impl Strategy<Value = Rectangle> {
+    (coordinate(), coordinate(), coordinate(), coordinate()).prop_map(|(x, y, width, height)| {
+        Rectangle {
+            x,
+            y,
+            width,
+            height,
+        }
+    })
+}
+
+/// Clip rectangles as set by `Display::set_clip`.
+fn clip() -> impl Strategy<Value = Option<Rectangle>> {
+    rectangle().prop_map(|area| area.intersection(&BOUNDING_BOX))
+}
+
+/// Signed coordinates of `embedded-graphics`.
+fn signed() -> impl Strategy<Value = i32> {
+    prop_oneof![4 => -1100i32..1100, 1 => any::<i32>()]
+}
+
+fn unsigned() -> impl Strategy<Value = u32> {
+    prop_oneof![4 => 0u32..1100, 1 => any::<u32>()]
+}
+
+/// Framebuffer with a pattern, so every write changes some bits.
+fn framebuffer<D: PixelDepth>() -> Vec<u8> {
+    (0..D::FRAMEBUFFER_SIZE)
+        .map(|i| (i * 37 + i / 1000) as u8)
+        .collect()
+}
+
+pub(super) fn pixel<D: PixelDepth>(buffer: &[u8], x: u16, y: u16) -> u8 {
+    let x = x as usize;
+    let byte = buffer[y as usize * D::LINE_BYTES + x / D::PIXELS_PER_BYTE];
+    (byte >> ((x % D::PIXELS_PER_BYTE) * D::BITS)) & D::MAX_COLOR
+}
+
+fn contains(area: Option<Rectangle>, x: u16, y: u16) -> bool {
+    area.is_some_and(|area| area.contains(x, y))
+}
+
+fn check_set_pixel<D: PixelDepth>(clip: Option<Rectangle>, x: u16, y: u16, color: u8) {
+    let original = framebuffer::<D>();
+    let mut buffer = original.clone();
+    let result = set_pixel::<D>(&mut buffer, clip, x, y, color);
+    if x >= WIDTH || y >= HEIGHT {
+        assert_eq!(result, Err(Error::OutOfBounds));
+    } else if color > D::MAX_COLOR {
+        assert_eq!(result, Err(Error::InvalidColor));
+    } else if contains(clip, x, y) {
+        assert_eq!(result, Ok(true));
+        assert_eq!(pixel::<D>(&buffer, x, y), color);
+        // the other pixels of the byte are kept
+        let index = y as usize * D::LINE_BYTES + x as usize / D::PIXELS_PER_BYTE;
+        for p in 0..D::PIXELS_PER_BYTE as u16 {
+            let other = x - x % D::PIXELS_PER_BYTE as u16 + p;
+            if other != x {
+                assert_eq!(
+                    pixel::<D>(&buffer, other, y),
+                    pixel::<D>(&original, other, y)
+                );
+            }
+        }
+        assert_eq!(buffer[..index], original[..index]);
+        assert_eq!(buffer[index + 1..], original[index + 1..]);
+        return;
+    } else {
+        assert_eq!(result, Ok(false));
+    }
+    assert!(buffer == original);
+}
+
+/// Fills with `color` and with `color` limited to the valid range.
+fn check_fill_rect<D: PixelDepth>(clip: Option<Rectangle>, area: Rectangle, color: u8) {
+    if color > D::MAX_COLOR {
+        check_fill_rect_color::<D>(clip, area, color);
+    }
+    check_fill_rect_color::<D>(clip, area, color & D::MAX_COLOR);
+}
+
+fn check_fill_rect_color<D: PixelDepth>(clip: Option<Rectangle>, area: Rectangle, color: u8) {
+    let original = framebuffer::<D>();
+    let mut buffer = original.clone();
+    let result = fill_rect::<D>(&mut buffer, clip, area, color);
+    if color > D::MAX_COLOR {
+        assert_eq!(result, Err(Error::InvalidColor));
+        assert!(buffer == original);
+        return;
+    }
+    let filled = result.unwrap();
+    assert_eq!(filled, clip.and_then(|clip| clip.intersection(&area)));
+    for (y, (row, original)) in buffer
+        .chunks(D::LINE_BYTES)
+        .zip(original.chunks(D::LINE_BYTES))
+        .enumerate()
+    {
+        let y = y as u16;
+        if !filled.is_some_and(|filled| (filled.y..filled.y + filled.height).contains(&y)) {
+            assert!(row == original, "row {y}");
+            continue;
+        }
+        for x in 0..WIDTH {
+            let expected = match contains(clip, x, y) && area.contains(x, y) {
+                true => color,
+                false => pixel::<D>(original, x, 0),
+            };
+            assert_eq!(pixel::<D>(row, x, 0), expected, "pixel {x}, {y}");
+        }
+    }
+}
+
+/// Returns `true` if the point is inside the area with signed coordinates.
+fn signed_contains(x: i32, y: i32, width: u32, height: u32, px: i32, py: i32) -> bool {
+    let inside = |start: i32, len: u32, p: i32| {
+        (start as i64..start as i64 + len as i64).contains(&(p as i64))
+    };
+    inside(x, width, px) && inside(y, height, py)
+}
+
+proptest! {
+    #[test]
+    fn intersection_contains_points_of_both(
+        a in rectangle(),
+        b in rectangle(),
+        x in coordinate(),
+        y in coordinate(),
+    ) {
+        let intersection = a.intersection(&b);
+        prop_assert_eq!(
+            contains(intersection, x, y),
+            a.contains(x, y) && b.contains(x, y)
+        );
+        prop_assert_eq!(intersection, b.intersection(&a));
+        if let Some(intersection) = intersection {
+            prop_assert!(intersection.width > 0 && intersection.height > 0);
+            prop_assert_eq!(a.intersection(&intersection), Some(intersection));
+        }
+    }
+
+    #[test]
+    fn clip_is_inside_screen(clip in clip(), x in coordinate(), y in coordinate()) {
+        if let Some(clip) = clip {
+            prop_assert!(clip.width > 0 && clip.height > 0);
+            prop_assert!(clip.x as u32 + clip.width as u32 <= WIDTH as u32);
+            prop_assert!(clip.y as u32 + clip.height as u32 <= HEIGHT as u32);
+        }
+        if contains(clip, x, y) {
+            prop_assert!(BOUNDING_BOX.contains(x, y));
+        }
+    }
+
+    #[test]
+    fn set_pixel_respects_bounds_and_clip(
+        clip in clip(),
+        x in coordinate(),
+        y in coordinate(),
+        color in prop_oneof![4 => 0u8..16, 1 => any::<u8>()],
+    ) {
+        check_set_pixel::<Bpp1>(clip, x, y, color);
+        check_set_pixel::<Bpp2>(clip, x, y, color);
+        check_set_pixel::<Bpp4>(clip, x, y, color);
+    }
+
+    #[test]
+    fn set_pixel_inside_clip(
+        clip in clip().prop_filter("empty clip", Option::is_some),
+        dx in any::<u16>(),
+        dy in any::<u16>(),
+        color in 0u8..16,
+    ) {
+        // pick a point of the clip rectangle, which random points rarely hit
+        let clip = clip.unwrap();
+        let x = clip.x + dx % clip.width;
+        let y = clip.y + dy % clip.height;
+        check_set_pixel::<Bpp1>(Some(clip), x, y, color & 0x01);
+        check_set_pixel::<Bpp2>(Some(clip), x, y, color & 0x03);
+        check_set_pixel::<Bpp4>(Some(clip), x, y, color);
+    }
+
+    #[test]
+    fn fill_rect_fills_area_inside_clip(
+        clip in clip(),
+        area in rectangle(),
+        color in prop_oneof![4 => 0u8..16, 1 => any::<u8>()],
+    ) {
+        check_fill_rect::<Bpp1>(clip, area, color);
+        check_fill_rect::<Bpp2>(clip, area, color);
+        check_fill_rect::<Bpp4>(clip, area, color);
+    }
+
+    #[test]
+    fn screen_point_is_inside_screen(x in signed(), y in signed()) {
+        let point = screen_point(x, y);
+        let inside = (0..WIDTH as i32).contains(&x) && (0..HEIGHT as i32).contains(&y);
+        prop_assert_eq!(point.is_some(), inside);
+        if let Some(point) = point {
+            prop_assert_eq!(point, (x as u16, y as u16));
+            // drawing never fails because of the bounds
+            check_set_pixel::<Bpp4>(Some(BOUNDING_BOX), point.0, point.1, 0);
+        }
+    }
+
+    #[test]
+    fn screen_area_clamps_to_screen(
+        x in signed(),
+        y in signed(),
+        width in unsigned(),
+        height in unsigned(),
+        px in 0u16..1100,
+        py in 0u16..1100,
+    ) {
+        let area = screen_area(x, y, width, height);
+        prop_assert!(area.x as u32 + area.width as u32 <= WIDTH as u32);
+        prop_assert!(area.y as u32 + area.height as u32 <= HEIGHT as u32);
+        prop_assert_eq!(
+            area.contains(px, py),
+            BOUNDING_BOX.contains(px, py)
+                && signed_contains(x, y, width, height, px as i32, py as i32)
+        );
+    }
+}
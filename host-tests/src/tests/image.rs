@@ -0,0 +1,217 @@
+use proptest::prelude::*;
+
+use super::random;
+use crate::{
+    codec::decode,
+    depth::{Bpp4, PixelDepth},
+    framebuffer::{write_pixel, Rectangle, BOUNDING_BOX},
+    image::Image4bpp,
+    Error,
+};
+
+fn encode(
+    width: u16,
+    height: u16,
+    data: &[u8],
+    transparent: Option<u8>,
+    compress: bool,
+) -> Vec<u8> {
+    let mut out = vec![0; Image4bpp::HEADER_SIZE + 2 * data.len() + 8];
+    let len = Image4bpp::encode(width, height, data, transparent, compress, &mut out).unwrap();
+    out.truncate(len);
+    out
+}
+
+fn stride(width: u16) -> usize {
+    (width as usize).div_ceil(2)
+}
+
+#[test]
+fn round_trip() {
+    for (width, height) in [(1, 1), (7, 3), (8, 5), (0, 4), (960, 2)] {
+        let data = random::bytes(stride(width) * height as usize, width as u32);
+        for transparent in [None, Some(0), Some(0x0F)] {
+            for compress in [false, true] {
+                let encoded = encode(width, height, &data, transparent, compress);
+                let image = Image4bpp::new(&encoded).unwrap();
+                assert_eq!((image.width(), image.height()), (width, height));
+                assert_eq!(image.stride(), stride(width));
+                assert_eq!(image.transparent(), transparent);
+                assert_eq!(image.is_compressed(), compress);
+                let mut pixels = vec![0; data.len()];
+                match compress {
+                    true => decode(image.data(), &mut pixels).unwrap(),
+                    false => pixels.copy_from_slice(image.data()),
+                }
+                assert_eq!(pixels, data);
+            }
+        }
+    }
+}
+
+#[test]
+fn header_layout() {
+    let encoded = encode(0x0102, 1, &[0xAB; 0x81], Some(9), true);
+    assert_eq!(encoded[..12], *b"EPI4\x01\x03\x09\x00\x02\x01\x01\x00");
+    let encoded = encode(3, 2, &[0x12, 0x34, 0x56, 0x78], None, false);
+    assert_eq!(encoded[..12], *b"EPI4\x01\x00\x00\x00\x03\x00\x02\x00");
+    assert_eq!(encoded[12..], [0x12, 0x34, 0x56, 0x78]);
+}
+
+#[test]
+fn encode_rejects_invalid_input() {
+    let data = [0x55; 6];
+    let mut out = [0; 64];
+    for (width, height, len) in [(3, 2, 5), (3, 2, 6), (4, 3, 5)] {
+        for compress in [false, true] {
+            assert_eq!(
+                Image4bpp::encode(width, height, &data[..len], None, compress, &mut out),
+                Err(Error::SizeMismatch),
+                "{width}x{height} with {len} bytes"
+            );
+        }
+    }
+    assert_eq!(
+        Image4bpp::encode(3, 2, &data[..4], Some(0x10), false, &mut out),
+        Err(Error::InvalidColor)
+    );
+    for compress in [false, true] {
+        for len in [0, Image4bpp::HEADER_SIZE - 1, Image4bpp::HEADER_SIZE + 1] {
+            assert_eq!(
+                Image4bpp::encode(3, 2, &data[..4], None, compress, &mut out[..len]),
+                Err(Error::BufferTooSmall),
+                "{len} bytes, compressed: {compress}"
+            );
+        }
+    }
+}
+
+#[test]
+fn new_rejects_invalid_images() {
+    let data = random::bytes(4 * 3, 1);
+    let raw = encode(7, 3, &data, Some(2), false);
+    assert!(Image4bpp::new(&raw).is_ok());
+    for (index, value) in [(0, b'X'), (3, b'8'), (4, 0), (4, 2), (6, 0x10)] {
+        let mut image = raw.clone();
+        image[index] = value;
+        assert_eq!(
+            Image4bpp::new(&image),
+            Err(Error::InvalidEncoding),
+            "byte {index}"
+        );
+    }
+    // uncompressed pixel data has to match the size
+    for len in [0, Image4bpp::HEADER_SIZE - 1, raw.len() - 1] {
+        assert_eq!(
+            Image4bpp::new(&raw[..len]),
+            Err(Error::InvalidEncoding),
+            "{len} bytes"
+        );
+    }
+    let mut longer = raw.clone();
+    longer.push(0);
+    assert_eq!(Image4bpp::new(&longer), Err(Error::InvalidEncoding));
+
+    // compressed pixel data is validated while drawing
+    let compressed = encode(7, 3, &data, None, true);
+    let image = Image4bpp::new(&compressed[..compressed.len() - 1]).unwrap();
+    let mut buffer = vec![0; Bpp4::FRAMEBUFFER_SIZE];
+    let area = Rectangle {
+        x: 0,
+        y: 0,
+        width: 7,
+        height: 3,
+    };
+    assert_eq!(
+        image.draw(&mut buffer, 0, 0, area),
+        Err(Error::InvalidEncoding)
+    );
+}
+
+/// Writes every visible pixel of the image on its own.
+#[allow(clippy::too_many_arguments)]
+fn reference(
+    buffer: &mut [u8],
+    data: &[u8],
+    width: u16,
+    height: u16,
+    transparent: Option<u8>,
+    x: u16,
+    y: u16,
+    target: Rectangle,
+) {
+    for j in 0..height {
+        for i in 0..width {
+            let (pixel_x, pixel_y) = (x as u32 + i as u32, y as u32 + j as u32);
+            if pixel_x > u16::MAX as u32
+                || pixel_y > u16::MAX as u32
+                || !target.contains(pixel_x as u16, pixel_y as u16)
+            {
+                continue;
+            }
+            let index = j as usize * stride(width) + i as usize / 2;
+            let color = data[index] >> (4 * (i % 2)) & 0x0F;
+            if transparent != Some(color) {
+                write_pixel::<Bpp4>(buffer, pixel_x as usize, pixel_y as usize, color);
+            }
+        }
+    }
+}
+
+/// Offsets of the images and clip rectangles, at the origin and near the
+/// right and bottom edges of the screen.
+fn origin() -> impl Strategy<Value = (u16, u16)> {
+    prop_oneof![Just((0, 0)), Just((901, 500)), Just((940, 529))]
+}
+
+fn clip() -> impl Strategy<Value = Option<Rectangle>> {
+    prop_oneof![
+        1 => Just(Some(BOUNDING_BOX)),
+        4 => (origin(), 0u16..50, 0u16..25, 0u16..60, 0u16..25).prop_map(
+            |((x0, y0), x, y, width, height)| {
+                Rectangle {
+                    x: x0 + x,
+                    y: y0 + y,
+                    width,
+                    height,
+                }
+                .intersection(&BOUNDING_BOX)
+            }
+        ),
+    ]
+}
+
+proptest! {
+    #[test]
+    fn compressed_draw_matches_raw(
+        (x0, y0) in origin(),
+        (x, y) in (0u16..40, 0u16..20),
+        (width, height) in (0u16..48, 0u16..12),
+        transparent in proptest::option::of(0u8..16),
+        clip in clip(),
+        runs: bool,
+        seed: u32,
+    ) {
+        let (x, y) = (x0 + x, y0 + y);
+        let mut data = random::bytes(stride(width) * height as usize, seed);
+        if runs {
+            // runs of 7 bytes, which cross the rows
+            for (i, byte) in data.iter_mut().enumerate() {
+                *byte = (i / 7 * 0x35) as u8;
+            }
+        }
+        let area = Rectangle { x, y, width, height };
+        let Some(target) = clip.and_then(|clip| clip.intersection(&area)) else {
+            return Ok(());
+        };
+        let original = random::bytes(Bpp4::FRAMEBUFFER_SIZE, seed ^ 0x5555);
+        let mut expected = original.clone();
+        reference(&mut expected, &data, width, height, transparent, x, y, target);
+        for compress in [false, true] {
+            let encoded = encode(width, height, &data, transparent, compress);
+            let mut buffer = original.clone();
+            Image4bpp::new(&encoded).unwrap().draw(&mut buffer, x, y, target).unwrap();
+            prop_assert!(buffer == expected, "compressed: {}", compress);
+        }
+    }
+}
@@ -0,0 +1,14 @@
+mod blit;
+mod codec;
+mod dirty_rows;
+mod dither;
+mod framebuffer;
+mod image;
+mod panel_config;
+mod random;
+mod repair;
+mod screenshot;
+mod stream;
+mod timings;
+mod tps65185;
+mod waveform;
@@ -0,0 +1,158 @@
+use crate::{panel_config::PanelConfig, timings::Timings, tone::ToneCurve, Error};
+
+/// Offset of the frame count in the blob.
+const FRAME_COUNT: usize = 68;
+/// Offset of the tone curve in the blob.
+const TONE_CURVE: usize = 74;
+
+fn config() -> PanelConfig {
+    let mut timings = Timings::DEFAULT;
+    timings.contrast_cycles[3] = 1234;
+    timings.contrast_cycles_white[14] = 0xFFFF;
+    timings.frame_count = 9;
+    timings.clear_cycles = 7;
+    timings.clear_cycle_time = 300;
+    PanelConfig {
+        vcom_mv: 1560,
+        timings,
+        tone_curve: ToneCurve::new([
+            0, 6, 12, 19, 27, 37, 49, 63, 79, 97, 117, 139, 163, 190, 221, 255,
+        ])
+        .unwrap(),
+    }
+}
+
+/// Fletcher-16 checksum, independent of the driver.
+fn checksum(data: &[u8]) -> [u8; 2] {
+    let (a, b) = data.iter().fold((0u32, 0u32), |(a, b), &byte| {
+        let a = (a + byte as u32) % 255;
+        (a, (b + a) % 255)
+    });
+    [a as u8, b as u8]
+}
+
+/// Updates the checksum after modifying a blob.
+fn seal(bytes: &mut [u8]) {
+    let len = bytes.len();
+    let checksum = checksum(&bytes[..len - 2]);
+    bytes[len - 2..].copy_from_slice(&checksum);
+}
+
+#[test]
+fn round_trip() {
+    for config in [PanelConfig::new(0), PanelConfig::new(5110), config()] {
+        let bytes = config.to_bytes();
+        assert_eq!(bytes.len(), PanelConfig::SIZE);
+        assert_eq!(PanelConfig::from_bytes(&bytes), Ok(config));
+    }
+}
+
+#[test]
+fn layout() {
+    let bytes = config().to_bytes();
+    assert_eq!(bytes[..8], [b'E', b'P', b'4', b'7', 2, 0, 0x18, 0x06]);
+    // contrast cycles of both modes
+    assert_eq!(bytes[14..16], 1234u16.to_le_bytes());
+    assert_eq!(bytes[66..68], [0xFF, 0xFF]);
+    assert_eq!(bytes[FRAME_COUNT], 9);
+    assert_eq!(bytes[70..74], [7, 0, 0x2C, 0x01]);
+    assert_eq!(
+        bytes[TONE_CURVE..TONE_CURVE + 16],
+        *config().tone_curve.levels()
+    );
+    assert_eq!(bytes[90..], checksum(&bytes[..90]));
+}
+
+#[test]
+fn longer_blobs_are_accepted() {
+    // e.g. a whole flash page
+    let mut page = [0xFF; 256];
+    page[..PanelConfig::SIZE].copy_from_slice(&config().to_bytes());
+    assert_eq!(PanelConfig::from_bytes(&page), Ok(config()));
+}
+
+#[test]
+fn rejects_corrupted_blob() {
+    let bytes = config().to_bytes();
+    for i in 0..bytes.len() {
+        for bit in 0..8 {
+            let mut corrupted = bytes;
+            corrupted[i] ^= 1 << bit;
+            assert_eq!(
+                PanelConfig::from_bytes(&corrupted),
+                Err(Error::InvalidConfig),
+                "byte {i} bit {bit}"
+            );
+        }
+    }
+}
+
+#[test]
+fn rejects_truncated_blob() {
+    let bytes = config().to_bytes();
+    for len in 0..bytes.len() {
+        assert_eq!(
+            PanelConfig::from_bytes(&bytes[..len]),
+            Err(Error::InvalidConfig),
+            "{len} bytes"
+        );
+    }
+}
+
+#[test]
+fn rejects_unknown_version() {
+    for version in [0, 3, 0xFF] {
+        let mut bytes = config().to_bytes();
+        bytes[4] = version;
+        seal(&mut bytes);
+        assert_eq!(
+            PanelConfig::from_bytes(&bytes),
+            Err(Error::InvalidConfig),
+            "version {version}"
+        );
+    }
+}
+
+#[test]
+fn rejects_invalid_content() {
+    let mut bytes = config().to_bytes();
+    bytes[FRAME_COUNT] = Timings::MAX_FRAME_COUNT + 1;
+    seal(&mut bytes);
+    assert_eq!(PanelConfig::from_bytes(&bytes), Err(Error::InvalidConfig));
+
+    // the lightness decreases
+    let mut bytes = config().to_bytes();
+    bytes[TONE_CURVE + 5] = 0;
+    seal(&mut bytes);
+    assert_eq!(PanelConfig::from_bytes(&bytes), Err(Error::InvalidConfig));
+}
+
+#[test]
+fn reads_version_1() {
+    // VCOM of 1560mV written by the first version, which had no timings and
+    // tone curve yet
+    let bytes = [b'E', b'P', b'4', b'7', 1, 0, 0x18, 0x06, 0x20, 0xE3];
+    let config = PanelConfig::from_bytes(&bytes).unwrap();
+    assert_eq!(config.vcom_mv, 1560);
+    assert_eq!(config.timings, Timings::DEFAULT);
+    assert_eq!(config.tone_curve, ToneCurve::DEFAULT);
+    assert_eq!(config, PanelConfig::new(1560));
+
+    // the rest of a longer buffer is ignored
+    let mut page = [0xFF; 256];
+    page[..bytes.len()].copy_from_slice(&bytes);
+    assert_eq!(PanelConfig::from_bytes(&page), Ok(config));
+
+    for len in 0..bytes.len() {
+        assert_eq!(
+            PanelConfig::from_bytes(&bytes[..len]),
+            Err(Error::InvalidConfig)
+        );
+    }
+    let mut corrupted = bytes;
+    corrupted[6] ^= 1;
+    assert_eq!(
+        PanelConfig::from_bytes(&corrupted),
+        Err(Error::InvalidConfig)
+    );
+}
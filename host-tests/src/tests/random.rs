@@ -0,0 +1,27 @@
+//! Reproducible pseudo random test data.
+
+/// Xorshift generator of pseudo random bytes, the seed selects the sequence.
+pub(super) struct Xorshift(u32);
+
+impl Xorshift {
+    pub(super) fn new(seed: u32) -> Self {
+        // the state must not be zero
+        Xorshift(seed | 1)
+    }
+}
+
+impl Iterator for Xorshift {
+    type Item = u8;
+
+    fn next(&mut self) -> Option<u8> {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 17;
+        self.0 ^= self.0 << 5;
+        Some(self.0 as u8)
+    }
+}
+
+/// Returns `len` pseudo random bytes.
+pub(super) fn bytes(len: usize, seed: u32) -> Vec<u8> {
+    Xorshift::new(seed).take(len).collect()
+}
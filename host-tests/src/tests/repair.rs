@@ -0,0 +1,129 @@
+use crate::{
+    repair::{Action, Repair, RepairConfig, RepairStatus},
+    Error,
+};
+
+const CONFIG: RepairConfig = RepairConfig {
+    black_iterations: 2,
+    white_iterations: 3,
+    pulse_time: 7,
+    wait_ms: 11,
+};
+
+/// Performs a step and returns its action.
+fn step(repair: &mut Repair) -> (Option<Action>, RepairStatus) {
+    let mut action = None;
+    let status = repair
+        .advance(|step| {
+            action = Some(step);
+            Ok(())
+        })
+        .unwrap();
+    (action, status)
+}
+
+#[test]
+fn steps_through_phases() {
+    let mut repair = Repair::new(CONFIG);
+    let expected = [
+        (Action::Clear, RepairStatus::Wait(0)),
+        (Action::Black(7), RepairStatus::Wait(11)),
+        (Action::Black(7), RepairStatus::Wait(11)),
+        (Action::Clear, RepairStatus::Wait(0)),
+        (Action::White(7), RepairStatus::Wait(11)),
+        (Action::White(7), RepairStatus::Wait(11)),
+        (Action::White(7), RepairStatus::Wait(11)),
+        (Action::Clear, RepairStatus::Done),
+    ];
+    assert_eq!(repair.progress(), (0, 8));
+    for (i, (action, status)) in expected.into_iter().enumerate() {
+        assert!(!repair.is_finished());
+        assert_eq!(step(&mut repair), (Some(action), status), "step {i}");
+        assert_eq!(repair.progress(), (i as u32 + 1, 8));
+    }
+    assert!(repair.is_finished());
+    // finished routines don't touch the display
+    assert_eq!(step(&mut repair), (None, RepairStatus::Done));
+    assert_eq!(repair.progress(), (8, 8));
+}
+
+#[test]
+fn clears_without_pulses() {
+    let config = RepairConfig {
+        black_iterations: 0,
+        white_iterations: 0,
+        ..CONFIG
+    };
+    let mut repair = Repair::new(config);
+    assert_eq!(repair.progress(), (0, 3));
+    assert_eq!(
+        step(&mut repair),
+        (Some(Action::Clear), RepairStatus::Wait(0))
+    );
+    assert_eq!(
+        step(&mut repair),
+        (Some(Action::Clear), RepairStatus::Wait(0))
+    );
+    assert_eq!(step(&mut repair), (Some(Action::Clear), RepairStatus::Done));
+    assert_eq!(repair.progress(), (3, 3));
+}
+
+#[test]
+fn default_takes_63_steps() {
+    let mut repair = Repair::new(RepairConfig::default());
+    assert_eq!(repair.progress(), (0, 63));
+    let mut waited = 0;
+    while let (Some(_), RepairStatus::Wait(ms)) = step(&mut repair) {
+        waited += ms;
+    }
+    assert_eq!(repair.progress(), (63, 63));
+    // about 30 seconds
+    assert_eq!(waited, 60 * 500);
+}
+
+#[test]
+fn cancel_clears_the_screen() {
+    for steps in [0, 1, 3, 7] {
+        let mut repair = Repair::new(CONFIG);
+        for _ in 0..steps {
+            step(&mut repair);
+        }
+        repair.cancel();
+        assert!(!repair.is_finished());
+        assert_eq!(
+            step(&mut repair),
+            (Some(Action::Clear), RepairStatus::Cancelled),
+            "cancelled after {steps} steps"
+        );
+        assert!(repair.is_finished());
+        assert_eq!(step(&mut repair), (None, RepairStatus::Cancelled));
+        // the clear of the cancellation is not a step of the routine
+        assert_eq!(repair.progress(), (steps, 8));
+    }
+}
+
+#[test]
+fn cancel_after_done_has_no_effect() {
+    let mut repair = Repair::new(CONFIG);
+    while step(&mut repair).1 != RepairStatus::Done {}
+    repair.cancel();
+    assert_eq!(step(&mut repair), (None, RepairStatus::Done));
+    assert_eq!(repair.progress(), (8, 8));
+}
+
+#[test]
+fn failed_step_is_repeated() {
+    let mut repair = Repair::new(CONFIG);
+    step(&mut repair);
+    assert_eq!(repair.advance(|_| Err(Error::Timeout)), Err(Error::Timeout));
+    assert_eq!(repair.progress(), (1, 8));
+    assert_eq!(
+        step(&mut repair),
+        (Some(Action::Black(7)), RepairStatus::Wait(11))
+    );
+
+    // a cancelled routine is finished even if clearing fails
+    repair.cancel();
+    assert_eq!(repair.advance(|_| Err(Error::Timeout)), Err(Error::Timeout));
+    assert!(repair.is_finished());
+}
@@ -0,0 +1,173 @@
+use std::io::Cursor;
+
+use super::{framebuffer::pixel, random};
+use crate::{
+    depth::{Bpp1, Bpp2, Bpp4, PixelDepth},
+    framebuffer::{HEIGHT, WIDTH},
+    screenshot::{write_pgm, write_png},
+};
+
+fn pgm<D: PixelDepth>(framebuffer: &[u8]) -> Vec<u8> {
+    let mut out = Vec::new();
+    write_pgm::<D, _>(framebuffer, &mut out).unwrap();
+    out
+}
+
+fn png<D: PixelDepth>(framebuffer: &[u8]) -> Vec<u8> {
+    let mut out = Vec::new();
+    write_png::<D, _>(framebuffer, &mut out).unwrap();
+    out
+}
+
+/// Bitwise CRC-32 of PNG chunks, independent of the table of the driver.
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = 0xFFFF_FFFF_u32;
+    for &byte in data {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = (crc >> 1) ^ (0xEDB8_8320 & (crc & 1).wrapping_neg());
+        }
+    }
+    !crc
+}
+
+fn adler32(data: &[u8]) -> u32 {
+    let (mut a, mut b) = (1u32, 0u32);
+    for &byte in data {
+        a = (a + byte as u32) % 65521;
+        b = (b + a) % 65521;
+    }
+    (b << 16) | a
+}
+
+/// Splits a PNG image into its chunks and checks their length and CRC.
+fn chunks(png: &[u8]) -> Vec<([u8; 4], &[u8])> {
+    assert_eq!(
+        png[..8],
+        [0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1A, b'\n']
+    );
+    let mut chunks = Vec::new();
+    let mut rest = &png[8..];
+    while !rest.is_empty() {
+        let len = u32::from_be_bytes(rest[..4].try_into().unwrap()) as usize;
+        assert!(rest.len() >= 12 + len, "chunk exceeds the image");
+        let kind: [u8; 4] = rest[4..8].try_into().unwrap();
+        let crc = u32::from_be_bytes(rest[8 + len..12 + len].try_into().unwrap());
+        assert_eq!(crc, crc32(&rest[4..8 + len]), "CRC of {:?}", kind);
+        chunks.push((kind, &rest[8..8 + len]));
+        rest = &rest[12 + len..];
+    }
+    chunks
+}
+
+/// Unpacks the stored blocks of a zlib stream, checking the block headers
+/// and the Adler-32 checksum.
+fn inflate_stored(zlib: &[u8]) -> Vec<u8> {
+    assert_eq!(((zlib[0] as u16) << 8 | zlib[1] as u16) % 31, 0);
+    assert_eq!(zlib[0] & 0x0F, 8, "deflate");
+    let mut data = Vec::new();
+    let mut rest = &zlib[2..];
+    loop {
+        let header = rest[0];
+        // stored blocks only, byte aligned as there are no other blocks
+        assert_eq!(header & 0x06, 0, "block type");
+        let len = u16::from_le_bytes([rest[1], rest[2]]);
+        let nlen = u16::from_le_bytes([rest[3], rest[4]]);
+        assert_eq!(nlen, !len);
+        data.extend_from_slice(&rest[5..5 + len as usize]);
+        rest = &rest[5 + len as usize..];
+        if header & 1 == 1 {
+            break;
+        }
+        // only the last block is shorter than the maximum
+        assert_eq!(len, 0xFFFF);
+    }
+    assert_eq!(rest, adler32(&data).to_be_bytes());
+    data
+}
+
+fn check_pgm<D: PixelDepth>(framebuffer: &[u8]) {
+    let mut expected = b"P5\n960 540\n255\n".to_vec();
+    for y in 0..HEIGHT {
+        for x in 0..WIDTH {
+            expected.push(pixel::<D>(framebuffer, x, y) * (0xFF / D::MAX_COLOR));
+        }
+    }
+    assert!(pgm::<D>(framebuffer) == expected);
+}
+
+fn check_png<D: PixelDepth>(framebuffer: &[u8]) {
+    let png = png::<D>(framebuffer);
+    let mut options = png::DecodeOptions::default();
+    options.set_ignore_adler32(false);
+    let mut reader = png::Decoder::new_with_options(Cursor::new(&png), options)
+        .read_info()
+        .unwrap();
+    let mut image = vec![0; reader.output_buffer_size().unwrap()];
+    let info = reader.next_frame(&mut image).unwrap();
+    assert_eq!((info.width, info.height), (WIDTH as u32, HEIGHT as u32));
+    assert_eq!(info.color_type, png::ColorType::Grayscale);
+    assert_eq!(info.bit_depth as usize, D::BITS);
+    assert_eq!(info.line_size, D::LINE_BYTES);
+    for (y, row) in image.chunks(info.line_size).enumerate() {
+        for x in 0..WIDTH {
+            // PNG stores the left pixel in the most significant bits
+            let x = x as usize;
+            let shift = 8 - D::BITS - (x % D::PIXELS_PER_BYTE) * D::BITS;
+            let value = (row[x / D::PIXELS_PER_BYTE] >> shift) & D::MAX_COLOR;
+            assert_eq!(
+                value,
+                pixel::<D>(framebuffer, x as u16, y as u16),
+                "pixel {x}, {y}"
+            );
+        }
+    }
+}
+
+fn check_png_stream<D: PixelDepth>(framebuffer: &[u8]) {
+    let png = png::<D>(framebuffer);
+    let chunks = chunks(&png);
+    let kinds: Vec<_> = chunks.iter().map(|(kind, _)| kind).collect();
+    assert_eq!(kinds, [b"IHDR", b"IDAT", b"IEND"]);
+    assert_eq!(chunks[2].1, []);
+
+    let raw = inflate_stored(chunks[1].1);
+    assert_eq!(raw.len(), HEIGHT as usize * (1 + D::LINE_BYTES));
+    for row in raw.chunks(1 + D::LINE_BYTES) {
+        // no filter
+        assert_eq!(row[0], 0);
+    }
+}
+
+#[test]
+fn pgm_matches_framebuffer() {
+    check_pgm::<Bpp1>(&random::bytes(Bpp1::FRAMEBUFFER_SIZE, 1));
+    check_pgm::<Bpp2>(&random::bytes(Bpp2::FRAMEBUFFER_SIZE, 2));
+    check_pgm::<Bpp4>(&random::bytes(Bpp4::FRAMEBUFFER_SIZE, 3));
+}
+
+#[test]
+fn pgm_of_white_framebuffer() {
+    let framebuffer = vec![0xFF; Bpp4::FRAMEBUFFER_SIZE];
+    let pgm = pgm::<Bpp4>(&framebuffer);
+    assert_eq!(pgm[..15], *b"P5\n960 540\n255\n");
+    assert_eq!(pgm.len(), 15 + 960 * 540);
+    assert!(pgm[15..].iter().all(|&value| value == 0xFF));
+}
+
+#[test]
+fn png_decodes_to_framebuffer() {
+    for seed in [4, 5] {
+        check_png::<Bpp1>(&random::bytes(Bpp1::FRAMEBUFFER_SIZE, seed));
+        check_png::<Bpp2>(&random::bytes(Bpp2::FRAMEBUFFER_SIZE, seed));
+        check_png::<Bpp4>(&random::bytes(Bpp4::FRAMEBUFFER_SIZE, seed));
+    }
+}
+
+#[test]
+fn png_splits_stored_blocks() {
+    // 1, 2 and 4 blocks of at most 0xFFFF bytes
+    check_png_stream::<Bpp1>(&random::bytes(Bpp1::FRAMEBUFFER_SIZE, 6));
+    check_png_stream::<Bpp2>(&random::bytes(Bpp2::FRAMEBUFFER_SIZE, 7));
+    check_png_stream::<Bpp4>(&random::bytes(Bpp4::FRAMEBUFFER_SIZE, 8));
+}
@@ -0,0 +1,512 @@
+use embedded_io::{ErrorKind, ErrorType, Read};
+use proptest::prelude::*;
+
+use super::random;
+use crate::{
+    depth::{Bpp1, Bpp2, Bpp4, PixelDepth},
+    dither::Dithering,
+    framebuffer::{write_pixel, Rectangle, BOUNDING_BOX, HEIGHT, WIDTH},
+    image::Image4bpp,
+    stream::Stream,
+    tone::ToneCurve,
+    Error,
+    Result,
+};
+
+/// Size of the test images, odd to get padded rows and half bytes.
+const IMAGE_WIDTH: usize = 23;
+const IMAGE_HEIGHT: usize = 11;
+
+/// Random 4-bit gray levels of an image, row by row.
+fn levels(seed: u32) -> Vec<u8> {
+    random::bytes(IMAGE_WIDTH * IMAGE_HEIGHT, seed)
+        .into_iter()
+        .map(|byte| byte & 0x0F)
+        .collect()
+}
+
+/// Binary PGM image of the gray levels, scaled to `max`.
+fn pgm(levels: &[u8], max: u16) -> Vec<u8> {
+    let mut out = format!("P5\n# comment\n{IMAGE_WIDTH} {IMAGE_HEIGHT}\n{max}\n").into_bytes();
+    out.extend(levels.iter().map(|&level| (level as u16 * max / 15) as u8));
+    out
+}
+
+/// Header of a BMP image with a `BITMAPINFOHEADER` extended to `info_size`
+/// bytes.
+#[allow(clippy::too_many_arguments)]
+fn bmp_header(
+    offset: u32,
+    info_size: u32,
+    width: i32,
+    height: i32,
+    bits: u16,
+    compression: u32,
+    colors: u32,
+) -> Vec<u8> {
+    let mut out = b"BM".to_vec();
+    // file size and reserved fields
+    out.extend([0; 8]);
+    out.extend(offset.to_le_bytes());
+    out.extend(info_size.to_le_bytes());
+    out.extend(width.to_le_bytes());
+    out.extend(height.to_le_bytes());
+    out.extend(1u16.to_le_bytes());
+    out.extend(bits.to_le_bytes());
+    out.extend(compression.to_le_bytes());
+    // image size, resolution, colors and important colors
+    out.extend([0; 12]);
+    out.extend(colors.to_le_bytes());
+    out.extend([0; 4]);
+    if info_size < 0x100 {
+        out.resize(14 + info_size as usize, 0);
+    }
+    out
+}
+
+/// BMP image of the gray levels, indexed images have a palette in reverse
+/// order and 1bpp images only black and white.
+fn bmp(levels: &[u8], bits: u16, top_down: bool) -> Vec<u8> {
+    let colors = match bits {
+        1 => 2,
+        4 | 8 => 16,
+        _ => 0,
+    };
+    let index = |level: u8| (15 - level as usize) * (colors - 1) / 15;
+    // a V2 header and a gap before the pixel data
+    let info_size = 52;
+    let offset = 14 + info_size + colors as u32 * 4 + 3;
+    let height = match top_down {
+        true => -(IMAGE_HEIGHT as i32),
+        false => IMAGE_HEIGHT as i32,
+    };
+    // 4bpp images use the default palette size
+    let count = if bits == 8 { colors as u32 } else { 0 };
+    let mut out = bmp_header(
+        offset,
+        info_size,
+        IMAGE_WIDTH as i32,
+        height,
+        bits,
+        0,
+        count,
+    );
+    for i in 0..colors {
+        let gray = ((colors - 1 - i) * 255 / (colors - 1)) as u8;
+        out.extend([gray, gray, gray, 0]);
+    }
+    out.extend([0xA5; 3]);
+
+    let mut rows: Vec<_> = levels.chunks(IMAGE_WIDTH).collect();
+    if !top_down {
+        rows.reverse();
+    }
+    for row in rows {
+        let start = out.len();
+        match bits {
+            24 | 32 => {
+                for &level in row {
+                    let gray = level * 17;
+                    out.extend([gray, gray, gray, 0xFF].iter().take(bits as usize / 8));
+                }
+            }
+            _ => {
+                let bits = bits as usize;
+                for pixels in row.chunks(8 / bits) {
+                    // the left pixel is stored in the most significant bits
+                    let byte = pixels.iter().enumerate().fold(0, |byte, (i, &level)| {
+                        byte | (index(level) << (8 - bits - i * bits))
+                    });
+                    out.push(byte as u8);
+                }
+            }
+        }
+        while !(out.len() - start).is_multiple_of(4) {
+            out.push(0);
+        }
+    }
+    out
+}
+
+/// Native image of the gray levels.
+fn native(levels: &[u8], transparent: Option<u8>, compress: bool) -> Vec<u8> {
+    let mut data = Vec::new();
+    for row in levels.chunks(IMAGE_WIDTH) {
+        data.extend(
+            row.chunks(2)
+                .map(|pixels| pixels[0] | pixels.get(1).map_or(0, |level| level << 4)),
+        );
+    }
+    let mut out = vec![0; Image4bpp::HEADER_SIZE + 2 * data.len()];
+    let len = Image4bpp::encode(
+        IMAGE_WIDTH as u16,
+        IMAGE_HEIGHT as u16,
+        &data,
+        transparent,
+        compress,
+        &mut out,
+    )
+    .unwrap();
+    out.truncate(len);
+    out
+}
+
+/// Images of the gray levels in every supported format, 1bpp BMP images
+/// need black and white levels.
+fn images(levels: &[u8]) -> Vec<(String, Vec<u8>)> {
+    let mut images = vec![
+        ("pgm".to_string(), pgm(levels, 255)),
+        ("pgm with max 15".to_string(), pgm(levels, 15)),
+        ("native".to_string(), native(levels, None, false)),
+        ("compressed native".to_string(), native(levels, None, true)),
+    ];
+    let bilevel = levels.iter().all(|&level| level == 0 || level == 15);
+    for bits in [1, 4, 8, 24, 32] {
+        if bits == 1 && !bilevel {
+            continue;
+        }
+        for top_down in [false, true] {
+            let name = format!("{bits}bpp BMP, top down: {top_down}");
+            images.push((name, bmp(levels, bits, top_down)));
+        }
+    }
+    images
+}
+
+/// Draws an image into `framebuffer` like `Display::draw_stream`, exact gray
+/// levels are not changed by dithering with the linear curve.
+fn draw<D: PixelDepth>(
+    framebuffer: &mut [u8],
+    clip: Option<Rectangle>,
+    mut image: &[u8],
+    x: u16,
+    y: u16,
+) -> Result<Rectangle> {
+    let mut stream = Stream::new(&mut image)?;
+    let area = stream.area(x, y);
+    if let Some(target) = clip.and_then(|clip| clip.intersection(&area)) {
+        stream.draw::<D>(
+            framebuffer,
+            x,
+            y,
+            target,
+            Dithering::FloydSteinberg,
+            &ToneCurve::LINEAR,
+        )?;
+    }
+    Ok(area)
+}
+
+/// Writes the gray levels at `x`, `y` into a copy of `framebuffer`, skipping
+/// pixels outside of `clip` and of the `transparent` level.
+fn expected<D: PixelDepth>(
+    framebuffer: &[u8],
+    clip: Option<Rectangle>,
+    levels: &[u8],
+    x: u16,
+    y: u16,
+    transparent: Option<u8>,
+) -> Vec<u8> {
+    let mut expected = framebuffer.to_vec();
+    for (i, &level) in levels.iter().enumerate() {
+        let pixel_x = x as usize + i % IMAGE_WIDTH;
+        let pixel_y = y as usize + i / IMAGE_WIDTH;
+        let visible = clip.is_some_and(|clip| {
+            pixel_x < WIDTH as usize
+                && pixel_y < HEIGHT as usize
+                && clip.contains(pixel_x as u16, pixel_y as u16)
+        });
+        if visible && transparent != Some(level) {
+            write_pixel::<D>(&mut expected, pixel_x, pixel_y, level >> (4 - D::BITS));
+        }
+    }
+    expected
+}
+
+fn check_images(levels: &[u8], clip: Option<Rectangle>, x: u16, y: u16) {
+    let framebuffer = random::bytes(Bpp4::FRAMEBUFFER_SIZE, 1);
+    let expected = expected::<Bpp4>(&framebuffer, clip, levels, x, y, None);
+    for (name, image) in images(levels) {
+        let mut actual = framebuffer.clone();
+        let area = draw::<Bpp4>(&mut actual, clip, &image, x, y).unwrap();
+        assert_eq!(
+            area,
+            Rectangle {
+                x,
+                y,
+                width: IMAGE_WIDTH as u16,
+                height: IMAGE_HEIGHT as u16
+            },
+            "{name}"
+        );
+        assert!(actual == expected, "{name} at {x}, {y} clipped to {clip:?}");
+    }
+}
+
+#[test]
+fn draws_every_format() {
+    check_images(&levels(2), Some(BOUNDING_BOX), 100, 50);
+    check_images(&levels(3), Some(BOUNDING_BOX), 0, 0);
+}
+
+#[test]
+fn draws_1bpp_bmp() {
+    let levels: Vec<_> = levels(4).iter().map(|&level| (level >> 3) * 15).collect();
+    check_images(&levels, Some(BOUNDING_BOX), 7, 3);
+}
+
+#[test]
+fn draws_at_screen_edges() {
+    check_images(&levels(5), Some(BOUNDING_BOX), WIDTH - 10, HEIGHT - 4);
+    check_images(&levels(6), Some(BOUNDING_BOX), WIDTH, HEIGHT);
+}
+
+#[test]
+fn skips_pixels_outside_of_clip() {
+    let clip = Rectangle {
+        x: 105,
+        y: 53,
+        width: 11,
+        height: 5,
+    };
+    check_images(&levels(7), Some(clip), 100, 50);
+    // cuts the left and the bottom rows
+    check_images(&levels(8), Some(clip), 110, 44);
+    check_images(&levels(9), None, 100, 50);
+    check_images(&levels(10), Some(clip), 300, 50);
+}
+
+#[test]
+fn skips_transparent_pixels() {
+    let levels = levels(11);
+    let framebuffer = random::bytes(Bpp4::FRAMEBUFFER_SIZE, 12);
+    let clip = Some(BOUNDING_BOX);
+    let expected = expected::<Bpp4>(&framebuffer, clip, &levels, 31, 17, Some(5));
+    for compress in [false, true] {
+        let mut actual = framebuffer.clone();
+        let image = native(&levels, Some(5), compress);
+        draw::<Bpp4>(&mut actual, clip, &image, 31, 17).unwrap();
+        assert!(actual == expected, "compressed: {compress}");
+    }
+}
+
+fn check_depth<D: PixelDepth>() {
+    // black and white survive the dithering at every depth
+    let bilevel: Vec<_> = levels(13).iter().map(|&level| (level & 1) * 15).collect();
+    let levels = levels(14);
+    let framebuffer = random::bytes(D::FRAMEBUFFER_SIZE, 15);
+    let clip = Some(BOUNDING_BOX);
+    for (levels, image) in [
+        (&bilevel, pgm(&bilevel, 255)),
+        (&bilevel, bmp(&bilevel, 24, false)),
+        (&levels, native(&levels, None, false)),
+        (&levels, native(&levels, None, true)),
+    ] {
+        let mut actual = framebuffer.clone();
+        draw::<D>(&mut actual, clip, &image, 9, 2).unwrap();
+        assert!(actual == expected::<D>(&framebuffer, clip, levels, 9, 2, None));
+    }
+}
+
+#[test]
+fn native_images_keep_upper_bits() {
+    check_depth::<Bpp1>();
+    check_depth::<Bpp2>();
+    check_depth::<Bpp4>();
+}
+
+#[test]
+fn pgm_values_are_limited_to_max() {
+    let mut image = b"P5 3 1 15\n".to_vec();
+    image.extend([15, 16, 0xFF]);
+    let mut framebuffer = vec![0; Bpp4::FRAMEBUFFER_SIZE];
+    draw::<Bpp4>(&mut framebuffer, Some(BOUNDING_BOX), &image, 0, 0).unwrap();
+    assert_eq!(framebuffer[..2], [0xFF, 0x0F]);
+}
+
+#[test]
+fn reading_stops_after_last_visible_row() {
+    let levels = levels(16);
+    let clip = Rectangle {
+        x: 0,
+        y: 0,
+        width: 100,
+        height: 3,
+    };
+    let framebuffer = vec![0; Bpp4::FRAMEBUFFER_SIZE];
+    let expected = expected::<Bpp4>(&framebuffer, Some(clip), &levels, 0, 0, None);
+    for (image, row_len) in [
+        (pgm(&levels, 255), IMAGE_WIDTH),
+        (native(&levels, None, false), IMAGE_WIDTH.div_ceil(2)),
+    ] {
+        let len = image.len() - (IMAGE_HEIGHT - 3) * row_len;
+        let mut actual = framebuffer.clone();
+        draw::<Bpp4>(&mut actual, Some(clip), &image[..len], 0, 0).unwrap();
+        assert!(actual == expected);
+    }
+}
+
+#[test]
+fn rejects_truncated_images() {
+    for (name, image) in images(&levels(17)) {
+        for len in 0..image.len() {
+            let mut framebuffer = vec![0; Bpp4::FRAMEBUFFER_SIZE];
+            let result = draw::<Bpp4>(&mut framebuffer, Some(BOUNDING_BOX), &image[..len], 0, 0);
+            assert_eq!(result, Err(Error::InvalidEncoding), "{name} of {len} bytes");
+        }
+    }
+}
+
+fn parse(mut image: &[u8]) -> Result<Rectangle> {
+    Stream::new(&mut image).map(|stream| stream.area(0, 0))
+}
+
+#[test]
+fn rejects_unknown_formats() {
+    assert_eq!(parse(b"GIF89a"), Err(Error::UnsupportedFormat));
+    // ASCII PGM
+    assert_eq!(parse(b"P2 1 1 255 0"), Err(Error::UnsupportedFormat));
+}
+
+#[test]
+fn rejects_malformed_pgm_headers() {
+    assert!(parse(b"P5\n# comment\n\n 3\t2\n255\n").is_ok());
+    for (header, error) in [
+        (&b"P5 3 2 0\n"[..], Error::InvalidEncoding),
+        (b"P5 0 2 255\n", Error::InvalidEncoding),
+        (b"P5 3 0 255\n", Error::InvalidEncoding),
+        (b"P5 x 2 255\n", Error::InvalidEncoding),
+        (b"P5 3 2 255x", Error::InvalidEncoding),
+        (b"P5 -3 2 255\n", Error::InvalidEncoding),
+        // 16-bit samples and too large images
+        (b"P5 3 2 256\n", Error::UnsupportedFormat),
+        (b"P5 65536 2 255\n", Error::UnsupportedFormat),
+        (b"P5 3 99999999999 255\n", Error::UnsupportedFormat),
+    ] {
+        assert_eq!(
+            parse(header),
+            Err(error),
+            "{:?}",
+            String::from_utf8_lossy(header)
+        );
+    }
+}
+
+#[test]
+fn rejects_malformed_bmp_headers() {
+    let offset = 14 + 40 + 16 * 4;
+    let valid = || bmp_header(offset, 40, 3, 2, 4, 0, 0);
+    let mut image = valid();
+    image.extend([0; 16 * 4]);
+    assert_eq!(parse(&image).map(|area| area.width), Ok(3));
+
+    for (header, error) in [
+        // OS/2 header, compression and 16bpp
+        (
+            bmp_header(offset, 12, 3, 2, 4, 0, 0),
+            Error::UnsupportedFormat,
+        ),
+        (
+            bmp_header(offset, 40, 3, 2, 4, 2, 0),
+            Error::UnsupportedFormat,
+        ),
+        (
+            bmp_header(offset, 40, 3, 2, 16, 0, 0),
+            Error::UnsupportedFormat,
+        ),
+        (
+            bmp_header(offset, 40, 0x1_0000, 2, 24, 0, 0),
+            Error::UnsupportedFormat,
+        ),
+        (
+            bmp_header(offset, 40, 0, 2, 4, 0, 0),
+            Error::InvalidEncoding,
+        ),
+        (
+            bmp_header(offset, 40, -3, 2, 4, 0, 0),
+            Error::InvalidEncoding,
+        ),
+        (
+            bmp_header(offset, 40, 3, 0, 4, 0, 0),
+            Error::InvalidEncoding,
+        ),
+        // more colors than an 8-bit palette
+        (
+            bmp_header(offset, 40, 3, 2, 8, 0, 257),
+            Error::InvalidEncoding,
+        ),
+        // pixel data inside the palette
+        (
+            bmp_header(offset - 1, 40, 3, 2, 4, 0, 0),
+            Error::InvalidEncoding,
+        ),
+        // the header or the pixel data exceed the stream
+        (
+            bmp_header(offset, u32::MAX, 3, 2, 4, 0, 0),
+            Error::InvalidEncoding,
+        ),
+        (
+            bmp_header(u32::MAX, 40, 3, 2, 4, 0, 0),
+            Error::InvalidEncoding,
+        ),
+    ] {
+        let mut image = header;
+        image.extend([0; 16 * 4 + 8]);
+        assert_eq!(parse(&image), Err(error));
+    }
+}
+
+#[test]
+fn rejects_malformed_native_headers() {
+    let image = native(&levels(18), Some(3), false);
+    assert!(parse(&image).is_ok());
+    for (offset, value) in [(2, b'X'), (4, 2), (6, 0x10)] {
+        let mut image = image.clone();
+        image[offset] = value;
+        assert_eq!(parse(&image), Err(Error::InvalidEncoding), "byte {offset}");
+    }
+    let mut image = image;
+    image[0] = b'X';
+    assert_eq!(parse(&image), Err(Error::UnsupportedFormat));
+}
+
+struct FailingReader;
+
+impl ErrorType for FailingReader {
+    type Error = ErrorKind;
+}
+
+impl Read for FailingReader {
+    fn read(&mut self, _buf: &mut [u8]) -> core::result::Result<usize, ErrorKind> {
+        Err(ErrorKind::TimedOut)
+    }
+}
+
+#[test]
+fn passes_read_errors() {
+    assert_eq!(
+        Stream::new(&mut FailingReader).err(),
+        Some(Error::Io(ErrorKind::TimedOut))
+    );
+}
+
+fn clip() -> impl Strategy<Value = Option<Rectangle>> {
+    (0u16..1000, 0u16..600, 0u16..100, 0u16..100).prop_map(|(x, y, width, height)| {
+        Rectangle {
+            x,
+            y,
+            width,
+            height,
+        }
+        .intersection(&BOUNDING_BOX)
+    })
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(64))]
+
+    #[test]
+    fn draws_clipped_images(seed: u32, clip in clip(), x in 0u16..1000, y in 0u16..600) {
+        check_images(&levels(seed), clip, x, y);
+    }
+}
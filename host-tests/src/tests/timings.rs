@@ -0,0 +1,28 @@
+use crate::{timings::Timings, Error};
+
+fn with_frame_count(frame_count: u8) -> Timings {
+    Timings {
+        frame_count,
+        ..Timings::DEFAULT
+    }
+}
+
+#[test]
+fn default_is_valid() {
+    assert_eq!(Timings::DEFAULT.validate(), Ok(()));
+    assert_eq!(Timings::default(), Timings::DEFAULT);
+}
+
+#[test]
+fn frame_count_is_limited() {
+    for frame_count in 1..=Timings::MAX_FRAME_COUNT {
+        assert_eq!(with_frame_count(frame_count).validate(), Ok(()));
+    }
+    for frame_count in [0, Timings::MAX_FRAME_COUNT + 1, 0xFF] {
+        assert_eq!(
+            with_frame_count(frame_count).validate(),
+            Err(Error::InvalidTimings),
+            "{frame_count} frames"
+        );
+    }
+}
@@ -0,0 +1,188 @@
+use embedded_hal::i2c::{ErrorKind, NoAcknowledgeSource};
+use embedded_hal_mock::eh1::{
+    delay::NoopDelay,
+    i2c::{Mock, Transaction},
+};
+
+use crate::{
+    tps65185::{Tps65185, DEFAULT_ADDRESS},
+    Error,
+};
+
+const TMST_VALUE: u8 = 0x00;
+const ENABLE: u8 = 0x01;
+const VCOM1: u8 = 0x03;
+const VCOM2: u8 = 0x04;
+const INT1: u8 = 0x07;
+const TMST1: u8 = 0x0D;
+const PG: u8 = 0x0F;
+
+/// Number of polls of the driver before it gives up.
+const POLLS: usize = 100;
+
+fn read(register: u8, value: u8) -> Transaction {
+    Transaction::write_read(DEFAULT_ADDRESS, vec![register], vec![value])
+}
+
+fn write(register: u8, value: u8) -> Transaction {
+    Transaction::write(DEFAULT_ADDRESS, vec![register, value])
+}
+
+/// Runs `test` against a PMIC which expects exactly the given transactions.
+fn with_pmic<T>(expectations: &[Transaction], test: impl FnOnce(&mut Tps65185<Mock>) -> T) -> T {
+    let mut pmic = Tps65185::new(Mock::new(expectations));
+    let result = test(&mut pmic);
+    pmic.release().done();
+    result
+}
+
+#[test]
+fn power_up_waits_for_all_rails() {
+    let expectations = [
+        write(ENABLE, 0xBF),
+        read(PG, 0x00),
+        // VB is still missing
+        read(PG, 0x7A),
+        // unused bits don't matter
+        read(PG, 0xFA),
+    ];
+    let result = with_pmic(&expectations, |pmic| pmic.power_up(&mut NoopDelay::new()));
+    assert_eq!(result, Ok(()));
+}
+
+#[test]
+fn power_up_times_out() {
+    let mut expectations = vec![write(ENABLE, 0xBF)];
+    // VNEG never comes up
+    expectations.extend(std::iter::repeat_n(read(PG, 0xF8), POLLS));
+    let result = with_pmic(&expectations, |pmic| pmic.power_up(&mut NoopDelay::new()));
+    assert_eq!(result, Err(Error::PowerGood));
+}
+
+#[test]
+fn power_good_checks_every_rail() {
+    for bit in [7, 6, 5, 4, 3, 1] {
+        let result = with_pmic(&[read(PG, !(1 << bit))], |pmic| pmic.power_good());
+        assert_eq!(result, Ok(false), "bit {bit}");
+    }
+    let result = with_pmic(&[read(PG, 0xFA)], |pmic| pmic.power_good());
+    assert_eq!(result, Ok(true));
+}
+
+#[test]
+fn power_down_enters_standby() {
+    let result = with_pmic(&[write(ENABLE, 0x7F)], |pmic| pmic.power_down());
+    assert_eq!(result, Ok(()));
+}
+
+#[test]
+fn set_vcom_splits_value() {
+    let cases = [
+        // millivolts, VCOM1, VCOM2 before, VCOM2 after
+        (0, 0x00, 0x01, 0x00),
+        (1560, 0x9C, 0x00, 0x00),
+        // rounded down to 10mV
+        (1569, 0x9C, 0x00, 0x00),
+        (2550, 0xFF, 0x01, 0x00),
+        (2560, 0x00, 0x00, 0x01),
+        // the other bits of VCOM2 are kept
+        (2570, 0x01, 0xC0, 0xC1),
+        (5110, 0xFF, 0x00, 0x01),
+    ];
+    for (millivolts, vcom1, before, after) in cases {
+        let expectations = [
+            write(VCOM1, vcom1),
+            read(VCOM2, before),
+            write(VCOM2, after),
+        ];
+        let result = with_pmic(&expectations, |pmic| pmic.set_vcom(millivolts));
+        assert_eq!(result, Ok(()), "{millivolts}mV");
+    }
+}
+
+#[test]
+fn set_vcom_rejects_out_of_range() {
+    let result = with_pmic(&[], |pmic| pmic.set_vcom(5111));
+    assert_eq!(result, Err(Error::InvalidVcom));
+}
+
+#[test]
+fn vcom_combines_registers() {
+    let cases = [
+        (0x00, 0x00, 0),
+        (0x9C, 0x00, 1560),
+        (0x00, 0x01, 2560),
+        // only bit 0 of VCOM2 belongs to the value
+        (0xFF, 0xFF, 5110),
+        (0x9C, 0xFE, 1560),
+    ];
+    for (vcom1, vcom2, millivolts) in cases {
+        let expectations = [read(VCOM1, vcom1), read(VCOM2, vcom2)];
+        let result = with_pmic(&expectations, |pmic| pmic.vcom());
+        assert_eq!(result, Ok(millivolts));
+    }
+}
+
+#[test]
+fn program_vcom_waits_for_completion() {
+    let expectations = [
+        write(VCOM1, 0x9C),
+        read(VCOM2, 0x00),
+        write(VCOM2, 0x00),
+        read(INT1, 0x00),
+        read(VCOM2, 0x00),
+        write(VCOM2, 0x40),
+        read(INT1, 0x00),
+        read(INT1, 0x01),
+    ];
+    let result = with_pmic(&expectations, |pmic| {
+        pmic.program_vcom(1560, &mut NoopDelay::new())
+    });
+    assert_eq!(result, Ok(()));
+}
+
+#[test]
+fn temperature_polls_conversion() {
+    let expectations = [
+        write(TMST1, 0x80),
+        read(TMST1, 0x80),
+        read(TMST1, 0x00),
+        read(TMST1, 0x20),
+        read(TMST_VALUE, 25),
+    ];
+    let result = with_pmic(&expectations, |pmic| {
+        pmic.temperature(&mut NoopDelay::new())
+    });
+    assert_eq!(result, Ok(25));
+}
+
+#[test]
+fn temperature_is_signed() {
+    let expectations = [
+        write(TMST1, 0x80),
+        read(TMST1, 0x20),
+        read(TMST_VALUE, 0xF6),
+    ];
+    let result = with_pmic(&expectations, |pmic| {
+        pmic.temperature(&mut NoopDelay::new())
+    });
+    assert_eq!(result, Ok(-10));
+}
+
+#[test]
+fn temperature_times_out() {
+    let mut expectations = vec![write(TMST1, 0x80)];
+    expectations.extend(std::iter::repeat_n(read(TMST1, 0x00), POLLS));
+    let result = with_pmic(&expectations, |pmic| {
+        pmic.temperature(&mut NoopDelay::new())
+    });
+    assert_eq!(result, Err(Error::Timeout));
+}
+
+#[test]
+fn i2c_errors_are_passed_through() {
+    let kind = ErrorKind::NoAcknowledge(NoAcknowledgeSource::Address);
+    let expectations = [Transaction::write(DEFAULT_ADDRESS, vec![ENABLE, 0xBF]).with_error(kind)];
+    let result = with_pmic(&expectations, |pmic| pmic.power_up(&mut NoopDelay::new()));
+    assert_eq!(result, Err(Error::I2c(kind)));
+}
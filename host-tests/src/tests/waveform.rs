@@ -0,0 +1,60 @@
+use crate::waveform::{scale_time, temperature_range, DEFAULT_TEMPERATURE_RANGE};
+
+#[test]
+fn temperature_range_boundaries() {
+    // below the first interval
+    assert_eq!(temperature_range(i8::MIN), 0);
+    assert_eq!(temperature_range(0), 0);
+    assert_eq!(temperature_range(14), 0);
+    // the upper bound of an interval belongs to the next one
+    for (temperature, range) in [
+        (15, 0),
+        (17, 0),
+        (18, 1),
+        (20, 1),
+        (21, 2),
+        (24, 3),
+        (27, 4),
+        (30, 5),
+        (33, 6),
+        (37, 6),
+    ] {
+        assert_eq!(temperature_range(temperature), range, "{temperature} °C");
+    }
+    // above the last interval
+    assert_eq!(temperature_range(38), 6);
+    assert_eq!(temperature_range(i8::MAX), 6);
+}
+
+#[test]
+fn default_range_is_room_temperature() {
+    assert_eq!(temperature_range(25), DEFAULT_TEMPERATURE_RANGE);
+}
+
+#[test]
+fn scale_time_keeps_reference_range() {
+    for time in [0, 1, 50, 220, 1000, u16::MAX] {
+        assert_eq!(scale_time(time, DEFAULT_TEMPERATURE_RANGE), time);
+    }
+}
+
+#[test]
+fn scale_time_by_phase_count() {
+    // 25, 22 and 15 phases
+    assert_eq!(scale_time(220, 0), 250);
+    assert_eq!(scale_time(220, 2), 220);
+    assert_eq!(scale_time(220, 6), 150);
+    // colder ranges never drive shorter
+    for time in [1, 100, 1000] {
+        for range in 1..7 {
+            assert!(scale_time(time, range - 1) >= scale_time(time, range));
+        }
+    }
+}
+
+#[test]
+fn scale_time_saturates() {
+    assert_eq!(scale_time(u16::MAX, 0), u16::MAX);
+    assert_eq!(scale_time(60_000, 0), u16::MAX);
+    assert_eq!(scale_time(u16::MAX, 6), (u16::MAX as u32 * 15 / 22) as u16);
+}
@@ -5,6 +5,8 @@ use esp_hal::{
     Blocking,
 };
 
+use crate::{Error, Result};
+
 pub struct Battery<'a, PIN>
 where
     PIN: AdcChannel + AnalogPin,
@@ -39,12 +41,11 @@ where
     }
 
     /// Read the current voltage of the battery
-    pub fn read(&mut self) -> f32 {
-        let v = self
-            .adc
-            .read_oneshot(&mut self.adc_pin)
-            .expect("unable to read oneshot from adc");
+    ///
+    /// Returns [Error::Adc] if the ADC conversion fails.
+    pub fn read(&mut self) -> Result<f32> {
+        let v = nb::block!(self.adc.read_oneshot(&mut self.adc_pin)).map_err(|_| Error::Adc)?;
 
-        (((v as f32) * 2.0) / 1000.0) * self.correction_factor
+        Ok((((v as f32) * 2.0) / 1000.0) * self.correction_factor)
     }
 }
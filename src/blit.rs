@@ -0,0 +1,155 @@
+//! Blitting of 4bpp image data with raster operations and masks.
+//!
+//! [Display::blit](crate::Display::blit) combines the source with the
+//! framebuffer a byte at a time, both pixels of a byte are combined at once.
+//! Source data which doesn't start at the same nibble as the target is shifted
+//! while blitting.
+
+use crate::{
+    depth::{Bpp4, PixelDepth},
+    framebuffer::Rectangle,
+    Error,
+    Result,
+};
+
+/// Combination of the source pixels with the pixels of the framebuffer.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum RasterOp {
+    /// Replaces the pixels with the source.
+    Copy,
+    /// Bitwise AND of source and framebuffer, darkens the framebuffer as
+    /// black is 0x0. A white source keeps the framebuffer.
+    And,
+    /// Bitwise OR of source and framebuffer, lightens the framebuffer as
+    /// white is 0xF. A black source keeps the framebuffer.
+    Or,
+    /// Bitwise XOR of source and framebuffer, blitting the same data twice
+    /// restores the framebuffer.
+    Xor,
+    /// Replaces the pixels with the inverted source.
+    Invert,
+    /// Replaces the pixels with the source, except for source pixels of the
+    /// given key color.
+    Transparent(u8),
+}
+
+impl RasterOp {
+    /// Combines a byte of the framebuffer with a byte of the source and
+    /// returns the result and the nibbles which may be changed.
+    fn apply(self, target: u8, source: u8) -> (u8, u8) {
+        match self {
+            RasterOp::Copy => (source, 0xFF),
+            RasterOp::And => (target & source, 0xFF),
+            RasterOp::Or => (target | source, 0xFF),
+            RasterOp::Xor => (target ^ source, 0xFF),
+            RasterOp::Invert => (!source, 0xFF),
+            RasterOp::Transparent(key) => {
+                // nibbles of the key color turn into zero
+                let diff = source ^ (key * 0x11);
+                let low = if diff & 0x0F != 0 { 0x0F } else { 0 };
+                let high = if diff & 0xF0 != 0 { 0xF0 } else { 0 };
+                (source, low | high)
+            }
+        }
+    }
+}
+
+/// 1bpp mask selecting the source pixels which are blitted. The mask covers
+/// the same area as the source and uses the layout of a
+/// [Bpp1](crate::Bpp1) framebuffer, i.e. the left pixel is stored in the
+/// least significant bit.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Mask<'a> {
+    /// Mask bits, pixels with a set bit are blitted.
+    pub data: &'a [u8],
+    /// Number of bytes between the start of two rows.
+    pub stride: usize,
+}
+
+/// Combines 4bpp image data with the part of `area` inside the `clip`
+/// rectangle of a [Bpp4] framebuffer and returns the changed area, see
+/// [Display::blit](crate::Display::blit).
+pub(crate) fn blit(
+    buffer: &mut [u8],
+    clip: Option<Rectangle>,
+    area: Rectangle,
+    data: &[u8],
+    stride: usize,
+    op: RasterOp,
+    mask: Option<Mask<'_>>,
+) -> Result<Option<Rectangle>> {
+    check_size(data, stride, (area.width as usize).div_ceil(2), area.height)?;
+    if let Some(mask) = mask {
+        check_size(
+            mask.data,
+            mask.stride,
+            (area.width as usize).div_ceil(8),
+            area.height,
+        )?;
+    }
+    if let RasterOp::Transparent(key) = op {
+        if key > 0x0F {
+            return Err(Error::InvalidColor);
+        }
+    }
+    let Some(target) = clip.and_then(|clip| clip.intersection(&area)) else {
+        return Ok(None);
+    };
+    let dx = (target.x - area.x) as usize;
+    let dy = (target.y - area.y) as usize;
+    let x_start = target.x as usize;
+    let x_end = x_start + target.width as usize;
+    // source and target pixels are stored in the same nibble of a byte
+    let aligned = (dx ^ x_start) & 1 == 0;
+    let rows = target.y as usize * Bpp4::LINE_BYTES
+        ..(target.y + target.height) as usize * Bpp4::LINE_BYTES;
+    for (row, line) in buffer[rows].chunks_exact_mut(Bpp4::LINE_BYTES).enumerate() {
+        let src = &data[(dy + row) * stride..];
+        let mask = mask.map(|mask| &mask.data[(dy + row) * mask.stride..]);
+        let bytes = x_start / 2..x_end.div_ceil(2);
+        for (index, byte) in bytes.clone().zip(&mut line[bytes]) {
+            // source position of the left pixel of the byte, -1 if the
+            // first pixel of the target is a right pixel
+            let x = index * 2;
+            let src_x = (x + dx) as isize - x_start as isize;
+            let source = match aligned {
+                true => src[src_x as usize / 2],
+                false => pixel(src, src_x) | pixel(src, src_x + 1) << 4,
+            };
+            let mut select = 0;
+            for p in 0..2 {
+                let inside = (x_start..x_end).contains(&(x + p));
+                if inside && mask.is_none_or(|mask| bit(mask, (src_x + p as isize) as usize)) {
+                    select |= 0x0F << (4 * p);
+                }
+            }
+            let (value, changed) = op.apply(*byte, source);
+            let select = select & changed;
+            *byte = (*byte & !select) | (value & select);
+        }
+    }
+    Ok(Some(target))
+}
+
+/// Checks that rows of `row_bytes` every `stride` bytes fit into `data`.
+fn check_size(data: &[u8], stride: usize, row_bytes: usize, height: u16) -> Result<()> {
+    let height = height as usize;
+    if height > 0 && (stride < row_bytes || data.len() < (height - 1) * stride + row_bytes) {
+        return Err(Error::SizeMismatch);
+    }
+    Ok(())
+}
+
+/// Returns the 4bpp pixel at `x` or 0 if it is outside of the row.
+fn pixel(row: &[u8], x: isize) -> u8 {
+    let Ok(x) = usize::try_from(x) else {
+        return 0;
+    };
+    row.get(x / 2)
+        .map_or(0, |byte| (byte >> (4 * (x % 2))) & 0x0F)
+}
+
+fn bit(mask: &[u8], x: usize) -> bool {
+    (mask[x / 8] >> (x % 8)) & 1 == 1
+}
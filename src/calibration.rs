@@ -0,0 +1,196 @@
+//! Panel calibration helpers.
+//!
+//! The image quality of the ED047TC1 depends on the panel specific VCOM
+//! voltage printed on the flex cable. On boards with a
+//! [TPS65185](crate::Tps65185) the voltage can be adjusted and calibrated
+//! using [Display::calibrate_vcom]. The gray levels differ between panel
+//! batches as well, [Display::calibrate_grayscale] determines the
+//! [Timings] and the [ToneCurve] of a panel. The results can be stored in a
+//! [PanelConfig] blob which can be persisted to flash or RTC memory.
+
+use embedded_hal::i2c::I2c;
+
+pub use crate::panel_config::PanelConfig;
+use crate::{
+    display::{Display, Timings},
+    dither::bayer_threshold,
+    tone::ToneCurve,
+    tps65185::Tps65185,
+    DrawMode,
+    Result,
+};
+
+/// Gray level patch shown by [Display::calibrate_grayscale].
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct GrayscalePatch {
+    /// Gray level of the patch.
+    pub level: u8,
+    /// Output time of the frame which is calibrated for this level.
+    pub cycles: u16,
+    /// Intended lightness of the level, white is 255.
+    pub target: u8,
+}
+
+/// Response to a [GrayscalePatch].
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum GrayscaleFeedback {
+    /// The patch matches the reference, use the current output time.
+    Accept,
+    /// Measured lightness of the patch, white is 255. After all candidates
+    /// have been measured, the output time closest to the target is used.
+    Lightness(u8),
+    /// Skip the current output time.
+    Next,
+}
+
+/// Result of [Display::calibrate_grayscale].
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct GrayscaleCalibration {
+    /// Timings with the calibrated [Timings::contrast_cycles].
+    pub timings: Timings,
+    /// Lightness of the gray levels.
+    pub tone_curve: ToneCurve,
+}
+
+impl<'a> Display<'a> {
+    /// Runs the VCOM calibration workflow.
+    ///
+    /// For every candidate voltage (in millivolts) the PMIC is set to the
+    /// voltage and a test pattern is drawn. The pattern consists of all 16
+    /// gray levels and a uniform mid gray area next to a checkerboard; with a
+    /// well matched VCOM both areas appear equally bright and the gray levels
+    /// are evenly spaced. `accept` is called after the pattern has been
+    /// flushed and should return `true` to pick the current voltage, e.g.
+    /// after an operator pressed a button.
+    ///
+    /// Returns the accepted voltage or `None` if no candidate was accepted.
+    /// The voltage is not persisted, use [Tps65185::program_vcom] and/or
+    /// [PanelConfig] to store it.
+    pub fn calibrate_vcom<I2C: I2c>(
+        &mut self,
+        pmic: &mut Tps65185<I2C>,
+        candidates: impl IntoIterator<Item = u16>,
+        mut accept: impl FnMut(u16) -> bool,
+    ) -> Result<Option<u16>> {
+        for vcom in candidates {
+            pmic.set_vcom(vcom)?;
+            self.clear()?;
+            self.draw_vcom_pattern()?;
+            self.flush(DrawMode::BlackOnWhite)?;
+            if accept(vcom) {
+                return Ok(Some(vcom));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Runs the grayscale calibration workflow.
+    ///
+    /// Gray level `n` is driven by the first `15 - n` frames of
+    /// [Timings::contrast_cycles], so the levels are calibrated from light to
+    /// dark, each one adjusting a single frame. For every level and candidate
+    /// output time a patch of the level is drawn next to a black and white
+    /// reference pattern with the intended lightness, then `feedback` is
+    /// called. An operator can [accept](GrayscaleFeedback::Accept) the patch
+    /// which looks like the reference, a light sensor can report the
+    /// [measured lightness](GrayscaleFeedback::Lightness) of the patch. If
+    /// neither happens for a level, the current output time is kept.
+    ///
+    /// Starts with the current timings of the display, which are not
+    /// changed. Use [Display::set_timings], [Display::set_tone_curve] and/or
+    /// [PanelConfig] to apply and store the result.
+    pub fn calibrate_grayscale(
+        &mut self,
+        candidates: &[u16],
+        mut feedback: impl FnMut(GrayscalePatch) -> GrayscaleFeedback,
+    ) -> Result<GrayscaleCalibration> {
+        let mut timings = *self.timings();
+        let mut levels = *self.tone_curve().levels();
+        levels[15] = 0xFF;
+        for level in (0..15u8).rev() {
+            let frame = 14 - level as usize;
+            let target = level * 17;
+            // output time and measured lightness of the best candidate
+            let mut best: Option<(u16, u8)> = None;
+            for &cycles in candidates {
+                let patch = GrayscalePatch {
+                    level,
+                    cycles,
+                    target,
+                };
+                timings.contrast_cycles[frame] = cycles;
+                self.clear()?;
+                self.draw_grayscale_pattern(level)?;
+                self.flush_with_timings(DrawMode::BlackOnWhite, &timings)?;
+                match feedback(patch) {
+                    GrayscaleFeedback::Accept => {
+                        best = Some((cycles, target));
+                        break;
+                    }
+                    GrayscaleFeedback::Lightness(lightness) => {
+                        let distance = |lightness: u8| lightness.abs_diff(target);
+                        if best.is_none_or(|(_, best)| distance(lightness) < distance(best)) {
+                            best = Some((cycles, lightness));
+                        }
+                    }
+                    GrayscaleFeedback::Next => {}
+                }
+            }
+            // the previous output time is restored if nothing was picked
+            let previous = self.timings().contrast_cycles[frame];
+            let (cycles, lightness) = best.unwrap_or((previous, levels[level as usize]));
+            debug!(
+                "calibration: level {} cycles {} lightness {}",
+                level, cycles, lightness
+            );
+            timings.contrast_cycles[frame] = cycles;
+            // measurements might be noisy, but darker levels can't be lighter
+            levels[level as usize] = lightness.min(levels[level as usize + 1]);
+        }
+        self.clear()?;
+        Ok(GrayscaleCalibration {
+            timings,
+            tone_curve: ToneCurve::new(levels)?,
+        })
+    }
+
+    fn draw_grayscale_pattern(&mut self, level: u8) -> Result<()> {
+        let half_width = Self::WIDTH / 2;
+        for y in 0..Self::HEIGHT {
+            for x in 0..Self::WIDTH {
+                let color = match x < half_width {
+                    true => level,
+                    false if level as i32 * 17 > bayer_threshold(x, y) => 0x0F,
+                    false => 0x00,
+                };
+                self.set_pixel(x, y, color)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn draw_vcom_pattern(&mut self) -> Result<()> {
+        self.fill(0x0F)?;
+        let bar_width = Self::WIDTH / 16;
+        let half_height = Self::HEIGHT / 2;
+        for y in 0..half_height {
+            for x in 0..Self::WIDTH {
+                self.set_pixel(x, y, (x / bar_width) as u8)?;
+            }
+        }
+        for y in half_height..Self::HEIGHT {
+            for x in 0..Self::WIDTH {
+                let color = match x < Self::WIDTH / 2 {
+                    true => 0x07,
+                    false if (x / 8 + y / 8) % 2 == 0 => 0x00,
+                    false => 0x0F,
+                };
+                self.set_pixel(x, y, color)?;
+            }
+        }
+        Ok(())
+    }
+}
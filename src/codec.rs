@@ -0,0 +1,276 @@
+//! Run-length and delta encoding of 4bpp framebuffer data.
+//!
+//! The codec works directly on the framebuffer layout of
+//! [Display](crate::Display), two pixels per byte with the left pixel in the
+//! lower nibble. Areas of a single color, like the background of a screen,
+//! shrink to a few bytes, while dithered images hardly compress. The codec
+//! does not allocate and can be used on the host to prepare images.
+//!
+//! The encoded stream is a sequence of tokens. Every token starts with a
+//! LEB128 encoded header `h`. If the lowest bit of `h` is cleared, the next
+//! byte is repeated `(h >> 1) + 1` times. Otherwise `(h >> 1) + 1` literal
+//! bytes follow.
+//!
+//! Delta encoding stores the XOR of a frame and a previous frame, so unchanged
+//! areas turn into long runs of zeros. Use [encode_delta] and [apply_delta].
+//!
+//! Large streams can be decoded in smaller chunks using a [Decoder].
+
+use crate::{Error, Result};
+
+/// Minimum number of equal bytes which are encoded as a run.
+const MIN_RUN: usize = 3;
+
+/// Encodes `data` into `out` and returns the number of bytes written.
+///
+/// Returns [Error::BufferTooSmall] if `out` can't hold the encoded data.
+pub fn encode(data: &[u8], out: &mut [u8]) -> Result<usize> {
+    encode_with(data.len(), |i| data[i], out)
+}
+
+/// Decodes `data` into `out`. The decoded data has to fill `out` exactly.
+///
+/// Returns [Error::InvalidEncoding] if the encoded data is malformed or
+/// doesn't match the size of `out`.
+pub fn decode(data: &[u8], out: &mut [u8]) -> Result<()> {
+    decode_with(
+        data,
+        out,
+        |target, value| target.fill(value),
+        |target, literal| target.copy_from_slice(literal),
+    )
+}
+
+/// Encodes the difference between `frame` and `previous` into `out` and
+/// returns the number of bytes written.
+///
+/// Returns [Error::SizeMismatch] if the frames differ in size and
+/// [Error::BufferTooSmall] if `out` can't hold the encoded data.
+pub fn encode_delta(frame: &[u8], previous: &[u8], out: &mut [u8]) -> Result<usize> {
+    if frame.len() != previous.len() {
+        return Err(Error::SizeMismatch);
+    }
+    encode_with(frame.len(), |i| frame[i] ^ previous[i], out)
+}
+
+/// Applies a difference created by [encode_delta] to `frame`, which has to
+/// contain the previous frame.
+///
+/// Returns [Error::InvalidEncoding] if the encoded data is malformed or
+/// doesn't match the size of `frame`. `frame` may be partially updated in
+/// that case.
+pub fn apply_delta(data: &[u8], frame: &mut [u8]) -> Result<()> {
+    decode_with(
+        data,
+        frame,
+        |target, value| target.iter_mut().for_each(|b| *b ^= value),
+        |target, literal| {
+            target
+                .iter_mut()
+                .zip(literal)
+                .for_each(|(b, literal)| *b ^= literal)
+        },
+    )
+}
+
+/// Incremental decoder for data created by [encode], decodes the stream in
+/// chunks of arbitrary size.
+pub struct Decoder<'a> {
+    reader: Reader<'a>,
+    remaining: usize,
+    literal: bool,
+    value: u8,
+}
+
+impl<'a> Decoder<'a> {
+    /// Creates a decoder for the encoded `data`.
+    pub fn new(data: &'a [u8]) -> Self {
+        Decoder {
+            reader: Reader { data, pos: 0 },
+            remaining: 0,
+            literal: false,
+            value: 0,
+        }
+    }
+
+    /// Decodes the next `out.len()` bytes into `out`.
+    ///
+    /// Returns [Error::InvalidEncoding] if the encoded data is malformed or
+    /// too short.
+    pub fn read(&mut self, out: &mut [u8]) -> Result<()> {
+        let mut pos = 0;
+        while pos < out.len() {
+            let len = self.next(out.len() - pos)?;
+            let target = &mut out[pos..pos + len];
+            match self.literal {
+                true => target.copy_from_slice(self.reader.bytes(len)?),
+                false => target.fill(self.value),
+            }
+            pos += len;
+        }
+        Ok(())
+    }
+
+    /// Skips the next `len` decoded bytes.
+    ///
+    /// Returns [Error::InvalidEncoding] if the encoded data is malformed or
+    /// too short.
+    pub fn skip(&mut self, mut len: usize) -> Result<()> {
+        while len > 0 {
+            let skipped = self.next(len)?;
+            if self.literal {
+                self.reader.bytes(skipped)?;
+            }
+            len -= skipped;
+        }
+        Ok(())
+    }
+
+    /// Returns `true` if all data has been decoded.
+    pub fn is_finished(&self) -> bool {
+        self.remaining == 0 && self.reader.is_empty()
+    }
+
+    /// Starts the next token if the current one is exhausted and takes up to
+    /// `max` bytes of it.
+    fn next(&mut self, max: usize) -> Result<usize> {
+        if self.remaining == 0 {
+            if self.reader.is_empty() {
+                return Err(Error::InvalidEncoding);
+            }
+            let header = self.reader.varint()?;
+            self.remaining = (header >> 1) as usize + 1;
+            self.literal = header & 1 == 1;
+            if !self.literal {
+                self.value = self.reader.bytes(1)?[0];
+            }
+        }
+        let len = self.remaining.min(max);
+        self.remaining -= len;
+        Ok(len)
+    }
+}
+
+/// Reads a LEB128 encoded token header, `next` returns the bytes of the
+/// stream.
+pub(crate) fn read_varint(mut next: impl FnMut() -> Result<u8>) -> Result<u32> {
+    let mut value = 0u32;
+    for shift in (0..32).step_by(7) {
+        let byte = next()?;
+        value |= ((byte & 0x7F) as u32) << shift;
+        if byte & 0x80 == 0 {
+            return Ok(value);
+        }
+    }
+    Err(Error::InvalidEncoding)
+}
+
+fn encode_with(len: usize, byte: impl Fn(usize) -> u8, out: &mut [u8]) -> Result<usize> {
+    let mut writer = Writer { out, pos: 0 };
+    let mut literal_start = 0;
+    let mut i = 0;
+    while i < len {
+        let value = byte(i);
+        let run = (i..len).take_while(|&j| byte(j) == value).count();
+        if run < MIN_RUN {
+            i += run;
+            continue;
+        }
+        if literal_start < i {
+            writer.literal((literal_start..i).map(&byte), i - literal_start)?;
+        }
+        writer.run(value, run)?;
+        i += run;
+        literal_start = i;
+    }
+    if literal_start < len {
+        writer.literal((literal_start..len).map(&byte), len - literal_start)?;
+    }
+    Ok(writer.pos)
+}
+
+fn decode_with(
+    data: &[u8],
+    out: &mut [u8],
+    run: impl Fn(&mut [u8], u8),
+    literal: impl Fn(&mut [u8], &[u8]),
+) -> Result<()> {
+    let mut pos = 0;
+    let mut reader = Reader { data, pos: 0 };
+    while !reader.is_empty() {
+        let header = reader.varint()?;
+        let len = (header >> 1) as usize + 1;
+        let target = out.get_mut(pos..pos + len).ok_or(Error::InvalidEncoding)?;
+        if header & 1 == 0 {
+            run(target, reader.bytes(1)?[0]);
+        } else {
+            literal(target, reader.bytes(len)?);
+        }
+        pos += len;
+    }
+    if pos != out.len() {
+        return Err(Error::InvalidEncoding);
+    }
+    Ok(())
+}
+
+struct Writer<'a> {
+    out: &'a mut [u8],
+    pos: usize,
+}
+
+impl Writer<'_> {
+    fn run(&mut self, value: u8, len: usize) -> Result<()> {
+        self.varint((len as u32 - 1) << 1)?;
+        self.byte(value)
+    }
+
+    fn literal(&mut self, data: impl Iterator<Item = u8>, len: usize) -> Result<()> {
+        self.varint(((len as u32 - 1) << 1) | 1)?;
+        for value in data {
+            self.byte(value)?;
+        }
+        Ok(())
+    }
+
+    fn varint(&mut self, mut value: u32) -> Result<()> {
+        loop {
+            let byte = (value & 0x7F) as u8;
+            value >>= 7;
+            if value == 0 {
+                return self.byte(byte);
+            }
+            self.byte(byte | 0x80)?;
+        }
+    }
+
+    fn byte(&mut self, value: u8) -> Result<()> {
+        *self.out.get_mut(self.pos).ok_or(Error::BufferTooSmall)? = value;
+        self.pos += 1;
+        Ok(())
+    }
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn is_empty(&self) -> bool {
+        self.pos >= self.data.len()
+    }
+
+    fn varint(&mut self) -> Result<u32> {
+        read_varint(|| Ok(self.bytes(1)?[0]))
+    }
+
+    fn bytes(&mut self, len: usize) -> Result<&'a [u8]> {
+        let data = self
+            .data
+            .get(self.pos..self.pos + len)
+            .ok_or(Error::InvalidEncoding)?;
+        self.pos += len;
+        Ok(data)
+    }
+}
@@ -0,0 +1,61 @@
+//! Pixel depths of the framebuffer.
+//!
+//! [Display](crate::Display) stores 4 bits per pixel ([Bpp4]) by default,
+//! which allows 16 gray levels. Screens which only need a few gray levels can
+//! use [Bpp2] or [Bpp1] instead. This shrinks the framebuffer to a half or a
+//! quarter and reduces the number of frames per flush to 3 or 1.
+//!
+//! Pixels are packed starting at the least significant bits of a byte, 0 is
+//! black and [PixelDepth::MAX_COLOR] is white.
+
+use crate::framebuffer::{HEIGHT, WIDTH};
+
+mod private {
+    pub trait Sealed {}
+}
+
+/// Pixel depth of a [Display](crate::Display).
+pub trait PixelDepth: private::Sealed {
+    /// Bits per pixel.
+    const BITS: usize;
+    /// Value of a white pixel, the highest allowed color.
+    const MAX_COLOR: u8 = ((1u16 << Self::BITS) - 1) as u8;
+    /// Number of pixels stored in a byte.
+    const PIXELS_PER_BYTE: usize = 8 / Self::BITS;
+    /// Size of a row of the framebuffer in bytes.
+    const LINE_BYTES: usize = WIDTH as usize / Self::PIXELS_PER_BYTE;
+    /// Size of the framebuffer in bytes.
+    const FRAMEBUFFER_SIZE: usize = Self::LINE_BYTES * HEIGHT as usize;
+
+    /// Memory of a framebuffer with this depth.
+    type Framebuffer: AsMut<[u8]>;
+}
+
+/// 1 bit per pixel, black and white. Used with `BinaryColor` of
+/// `embedded-graphics`, `On` pixels are black.
+pub struct Bpp1;
+
+/// 2 bits per pixel, 4 gray levels. Used with `Gray2` of `embedded-graphics`.
+pub struct Bpp2;
+
+/// 4 bits per pixel, 16 gray levels. Used with `Gray4` of `embedded-graphics`.
+pub struct Bpp4;
+
+impl private::Sealed for Bpp1 {}
+impl private::Sealed for Bpp2 {}
+impl private::Sealed for Bpp4 {}
+
+impl PixelDepth for Bpp1 {
+    const BITS: usize = 1;
+    type Framebuffer = [u8; Self::FRAMEBUFFER_SIZE];
+}
+
+impl PixelDepth for Bpp2 {
+    const BITS: usize = 2;
+    type Framebuffer = [u8; Self::FRAMEBUFFER_SIZE];
+}
+
+impl PixelDepth for Bpp4 {
+    const BITS: usize = 4;
+    type Framebuffer = [u8; Self::FRAMEBUFFER_SIZE];
+}
@@ -0,0 +1,95 @@
+//! Tracking of framebuffer rows which have to be sent to the panel.
+
+use core::ops::Range;
+
+use crate::framebuffer::HEIGHT;
+
+const ROWS: usize = HEIGHT as usize;
+const WORDS: usize = ROWS.div_ceil(32);
+
+/// Bitset with one bit per row of the screen, used to track the rows of the
+/// framebuffer which have to be sent to the panel. Rows outside of the screen
+/// are ignored.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct DirtyRows {
+    bits: [u32; WORDS],
+}
+
+impl DirtyRows {
+    /// Creates an empty set.
+    pub const fn new() -> Self {
+        DirtyRows { bits: [0; WORDS] }
+    }
+
+    /// Marks a single row as dirty.
+    pub fn insert(&mut self, row: u16) {
+        if (row as usize) < ROWS {
+            self.bits[row as usize / 32] |= 1 << (row % 32);
+        }
+    }
+
+    /// Marks all rows in `rows` as dirty.
+    pub fn insert_range(&mut self, rows: Range<u16>) {
+        let start = (rows.start as usize).min(ROWS);
+        let end = (rows.end as usize).min(ROWS);
+        let mut row = start;
+        while row < end {
+            let bit = row % 32;
+            let count = (32 - bit).min(end - row);
+            let mask = match count {
+                32 => u32::MAX,
+                _ => ((1u32 << count) - 1) << bit,
+            };
+            self.bits[row / 32] |= mask;
+            row += count;
+        }
+    }
+
+    /// Marks all rows of the screen as dirty.
+    pub fn insert_all(&mut self) {
+        self.insert_range(0..ROWS as u16);
+    }
+
+    /// Returns `true` if the row is dirty.
+    pub fn contains(&self, row: u16) -> bool {
+        (row as usize) < ROWS && self.bits[row as usize / 32] & (1 << (row % 32)) != 0
+    }
+
+    /// Returns `true` if no row is dirty.
+    pub fn is_empty(&self) -> bool {
+        self.bits.iter().all(|&word| word == 0)
+    }
+
+    /// Marks all rows as clean.
+    pub fn clear(&mut self) {
+        self.bits.fill(0);
+    }
+
+    /// Adds all dirty rows of `other` to this set.
+    pub fn union(&mut self, other: &DirtyRows) {
+        for (word, other) in self.bits.iter_mut().zip(other.bits) {
+            *word |= other;
+        }
+    }
+
+    /// Iterates over the dirty rows in ascending order.
+    pub fn iter(&self) -> impl Iterator<Item = u16> + '_ {
+        self.bits.iter().enumerate().flat_map(|(index, &word)| {
+            let mut word = word;
+            core::iter::from_fn(move || {
+                if word == 0 {
+                    return None;
+                }
+                let bit = word.trailing_zeros();
+                word &= word - 1;
+                Some((index * 32) as u16 + bit as u16)
+            })
+        })
+    }
+}
+
+impl Default for DirtyRows {
+    fn default() -> Self {
+        Self::new()
+    }
+}
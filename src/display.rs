@@ -1,29 +1,61 @@
-use alloc::{boxed::Box, vec, vec::Vec};
+#[cfg(feature = "alloc")]
+use alloc::{boxed::Box, vec};
+use core::{
+    marker::PhantomData,
+    ops::{Deref, DerefMut, Range},
+};
 
-use esp_hal::{delay::Delay, peripherals};
-use log::*;
+use embedded_hal::delay::DelayNs;
+use embedded_io::{Read, Write};
+use esp_hal::{
+    dma::TxChannelFor,
+    gpio::{interconnect::PeripheralOutput, OutputPin},
+    peripherals,
+};
 
-use crate::{ed047tc1, Error, Result};
-
-const CONTRAST_CYCLES_4BPP: &[u16; 15] = &[
-    30, 30, 20, 20, 30, 30, 30, 40, 40, 50, 50, 50, 100, 200, 300,
-];
-const CONTRAST_CYCLES_4BPP_WHITE: &[u16; 15] =
-    &[10, 10, 8, 8, 8, 8, 8, 10, 10, 15, 15, 20, 20, 100, 300];
+use crate::{
+    blit::{self, Mask, RasterOp},
+    codec,
+    depth::{Bpp4, PixelDepth},
+    dirty_rows::DirtyRows,
+    dither::Dithering,
+    ed047tc1::{self, PinConfig},
+    framebuffer::{self, write_pixel},
+    image::Image4bpp,
+    repair::{Action, Repair, RepairConfig, RepairStatus},
+    screenshot,
+    stream::Stream,
+    tone::ToneCurve,
+    waveform,
+    Error,
+    Result,
+};
+pub use crate::{framebuffer::Rectangle, timings::Timings};
 
 #[derive(Clone, Copy, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub enum DrawMode {
     BlackOnWhite,
     WhiteOnWhite,
     WhiteOnBlack,
 }
 
-#[derive(Clone, Copy, Debug)]
-pub struct Rectangle {
-    pub x: u16,
-    pub y: u16,
-    pub width: u16,
-    pub height: u16,
+/// Strategies to clear the screen, trading the visible flashing for speed.
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum ClearStrategy {
+    /// A single black/white flash. Fastest, but might leave ghosting.
+    Fast,
+    /// [Timings::clear_cycles] black/white flashes.
+    Thorough,
+    /// Like [ClearStrategy::Thorough], but each cycle pushes white before and
+    /// after the black phase, with the same total black and white drive
+    /// time. Dark content is driven towards white first, which reduces the DC
+    /// imbalance of the pixels.
+    InvertedFlash,
+    /// A thorough clear followed by darkening the area to the provided gray
+    /// level (0x0 - 0xF).
+    Gray(u8),
 }
 
 impl DrawMode {
@@ -34,158 +66,829 @@ impl DrawMode {
         }
     }
 
-    fn contrast_cycles(&self) -> &[u16; 15] {
+    fn contrast_cycles<'t>(&self, timings: &'t Timings) -> &'t [u16; 15] {
         match self {
-            Self::WhiteOnBlack => CONTRAST_CYCLES_4BPP_WHITE,
-            Self::BlackOnWhite | Self::WhiteOnWhite => CONTRAST_CYCLES_4BPP,
+            Self::WhiteOnBlack => &timings.contrast_cycles_white,
+            Self::BlackOnWhite | Self::WhiteOnWhite => &timings.contrast_cycles,
         }
     }
 }
 
-const TAINTED_ROWS_SIZE: usize = Display::HEIGHT as usize / 8 + 1;
-const FRAMEBUFFER_SIZE: usize = (Display::WIDTH / 2) as usize * Display::HEIGHT as usize;
+/// Size of the 4bpp framebuffer in bytes, see [PixelDepth::FRAMEBUFFER_SIZE]
+/// for other depths.
+pub const FRAMEBUFFER_SIZE: usize = Bpp4::FRAMEBUFFER_SIZE;
 const BYTES_PER_LINE: usize = Display::WIDTH as usize / 4;
-const LINE_BYTES_4BPP: usize = Display::WIDTH as usize / 2;
+/// Number of frames of a 4bpp flush, one per gray level transition.
+const DRAW_IMAGE_FRAME_COUNT: usize = 15;
+pub(crate) const BLACK: u16 = 0;
+pub(crate) const WHITE: u16 = 1;
+const SNAPSHOT_HEADER: [u8; 3] = [b'E', b'S', 1];
+
+/// Memory of a framebuffer, either provided by the caller or allocated.
+enum Buffer<'a> {
+    Borrowed(&'a mut [u8]),
+    #[cfg(feature = "alloc")]
+    Owned(Box<[u8]>),
+}
+
+impl Buffer<'_> {
+    /// Allocates a white framebuffer without building it on the stack first.
+    #[cfg(feature = "alloc")]
+    fn allocate(size: usize) -> Self {
+        Buffer::Owned(vec![0xFF; size].into_boxed_slice())
+    }
+}
+
+impl Deref for Buffer<'_> {
+    type Target = [u8];
+
+    fn deref(&self) -> &Self::Target {
+        match self {
+            Buffer::Borrowed(buffer) => buffer,
+            #[cfg(feature = "alloc")]
+            Buffer::Owned(buffer) => buffer,
+        }
+    }
+}
+
+impl DerefMut for Buffer<'_> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        match self {
+            Buffer::Borrowed(buffer) => buffer,
+            #[cfg(feature = "alloc")]
+            Buffer::Owned(buffer) => buffer,
+        }
+    }
+}
 
-pub struct Display<'a> {
+/// Driver of the display, storing `D` bits per pixel in the framebuffer.
+pub struct Display<'a, D: PixelDepth = Bpp4> {
     epd: ed047tc1::ED047TC1<'a>,
     skipping: u16,
-    framebuffer: Box<[u8; FRAMEBUFFER_SIZE]>,
-    tainted_rows: [u8; TAINTED_ROWS_SIZE],
+    framebuffer: Buffer<'a>,
+    dirty_rows: DirtyRows,
+    temperature_range: usize,
+    screen: Option<Buffer<'a>>,
+    timings: Timings,
+    clip: Option<Rectangle>,
+    tone_curve: ToneCurve,
+    depth: PhantomData<D>,
 }
 
 impl<'a> Display<'a> {
     /// Width of the screen.
-    pub const WIDTH: u16 = 960;
+    pub const WIDTH: u16 = framebuffer::WIDTH;
     /// Height of the screen
-    pub const HEIGHT: u16 = 540;
+    pub const HEIGHT: u16 = framebuffer::HEIGHT;
     /// Bounding Box of the screen.
-    pub const BOUNDING_BOX: Rectangle = Rectangle {
-        x: 0,
-        y: 0,
-        width: Self::WIDTH,
-        height: Self::HEIGHT,
-    };
-    pub fn new(
-        pins: ed047tc1::PinConfig<'a>,
-        dma: peripherals::DMA_CH0<'a>,
+    pub const BOUNDING_BOX: Rectangle = framebuffer::BOUNDING_BOX;
+
+    /// Creates a new display driver from the provided pins and peripherals.
+    /// The framebuffer is allocated on the heap, use
+    /// [Display::new_with_framebuffer] to provide the memory yourself.
+    ///
+    /// See [PinConfigV23](crate::PinConfigV23) and
+    /// [pin_config](crate::pin_config) for the default wiring of the V2.3
+    /// board. Any GDMA channel can be used for the LCD_CAM peripheral, which
+    /// allows `DMA_CH0` to be kept for other peripherals.
+    #[cfg(feature = "alloc")]
+    #[allow(clippy::type_complexity)]
+    pub fn new<D0, D1, D2, D3, D4, D5, D6, D7, CfgData, CfgClk, CfgStr, Dc, Wrx, Rmt>(
+        pins: PinConfig<D0, D1, D2, D3, D4, D5, D6, D7, CfgData, CfgClk, CfgStr, Dc, Wrx, Rmt>,
+        dma: impl TxChannelFor<peripherals::LCD_CAM<'a>>,
+        lcd_cam: peripherals::LCD_CAM<'a>,
+        rmt: peripherals::RMT<'a>,
+    ) -> Result<Self>
+    where
+        D0: PeripheralOutput<'a>,
+        D1: PeripheralOutput<'a>,
+        D2: PeripheralOutput<'a>,
+        D3: PeripheralOutput<'a>,
+        D4: PeripheralOutput<'a>,
+        D5: PeripheralOutput<'a>,
+        D6: PeripheralOutput<'a>,
+        D7: PeripheralOutput<'a>,
+        CfgData: OutputPin + 'a,
+        CfgClk: OutputPin + 'a,
+        CfgStr: OutputPin + 'a,
+        Dc: PeripheralOutput<'a>,
+        Wrx: PeripheralOutput<'a>,
+        Rmt: OutputPin + 'a,
+    {
+        Self::new_with_depth(pins, dma, lcd_cam, rmt)
+    }
+
+    /// Same as [Display::new], but uses the provided memory as framebuffer
+    /// and doesn't allocate. The memory can e.g. be a `static` placed in
+    /// PSRAM or internal SRAM, its content is reset to white.
+    #[allow(clippy::type_complexity)]
+    pub fn new_with_framebuffer<
+        D0,
+        D1,
+        D2,
+        D3,
+        D4,
+        D5,
+        D6,
+        D7,
+        CfgData,
+        CfgClk,
+        CfgStr,
+        Dc,
+        Wrx,
+        Rmt,
+    >(
+        pins: PinConfig<D0, D1, D2, D3, D4, D5, D6, D7, CfgData, CfgClk, CfgStr, Dc, Wrx, Rmt>,
+        framebuffer: &'a mut [u8; FRAMEBUFFER_SIZE],
+        dma: impl TxChannelFor<peripherals::LCD_CAM<'a>>,
+        lcd_cam: peripherals::LCD_CAM<'a>,
+        rmt: peripherals::RMT<'a>,
+    ) -> Result<Self>
+    where
+        D0: PeripheralOutput<'a>,
+        D1: PeripheralOutput<'a>,
+        D2: PeripheralOutput<'a>,
+        D3: PeripheralOutput<'a>,
+        D4: PeripheralOutput<'a>,
+        D5: PeripheralOutput<'a>,
+        D6: PeripheralOutput<'a>,
+        D7: PeripheralOutput<'a>,
+        CfgData: OutputPin + 'a,
+        CfgClk: OutputPin + 'a,
+        CfgStr: OutputPin + 'a,
+        Dc: PeripheralOutput<'a>,
+        Wrx: PeripheralOutput<'a>,
+        Rmt: OutputPin + 'a,
+    {
+        Self::new_with_depth_and_framebuffer(pins, framebuffer, dma, lcd_cam, rmt)
+    }
+
+    /// Combines 4bpp image data with the part of `area` inside the
+    /// [clip rectangle](Display::set_clip) using `op`. `data` uses the
+    /// nibble order of the framebuffer, every row of the image starts
+    /// `stride` bytes after the previous one. If a `mask` is provided, only
+    /// the pixels with a set mask bit are changed. The rows of the area are
+    /// marked as dirty.
+    ///
+    /// Returns [Error::SizeMismatch] if `stride` is too small for the width
+    /// of `area` or `data` or the mask are too short for the area and
+    /// [Error::InvalidColor] if the key color of [RasterOp::Transparent]
+    /// exceeds 0x0F.
+    pub fn blit(
+        &mut self,
+        area: Rectangle,
+        data: &[u8],
+        stride: usize,
+        op: RasterOp,
+        mask: Option<Mask<'_>>,
+    ) -> Result<()> {
+        let clip = self.clip;
+        if let Some(area) = blit::blit(&mut self.framebuffer, clip, area, data, stride, op, mask)? {
+            self.dirty_rows.insert_range(area.y..area.y + area.height);
+        }
+        Ok(())
+    }
+
+    /// Copies 4bpp image data into the part of `area` inside the
+    /// [clip rectangle](Display::set_clip). `data` uses the nibble order of
+    /// the framebuffer, every row of the image starts `stride` bytes after
+    /// the previous one. Same as [Display::blit] using [RasterOp::Copy].
+    ///
+    /// Returns [Error::SizeMismatch] if `stride` is too small for the width
+    /// of `area` or `data` is too short for the area.
+    pub fn blit_4bpp(&mut self, area: Rectangle, data: &[u8], stride: usize) -> Result<()> {
+        self.blit(area, data, stride, RasterOp::Copy, None)
+    }
+
+    /// Draws an [Image4bpp] with its top left corner at `x`, `y`. Only the
+    /// part inside the [clip rectangle](Display::set_clip) is drawn,
+    /// transparent pixels are skipped.
+    ///
+    /// Returns [Error::InvalidEncoding] if the compressed pixel data of the
+    /// image is corrupted, the image might be partially drawn in that case.
+    pub fn draw_image(&mut self, image: &Image4bpp<'_>, x: u16, y: u16) -> Result<()> {
+        let area = Rectangle {
+            x,
+            y,
+            width: image.width(),
+            height: image.height(),
+        };
+        let Some(target) = self.clip.and_then(|clip| clip.intersection(&area)) else {
+            return Ok(());
+        };
+        self.dirty_rows
+            .insert_range(target.y..target.y + target.height);
+        image.draw(&mut self.framebuffer, x, y, target)
+    }
+}
+
+impl<'a, D: PixelDepth> Display<'a, D> {
+    /// Same as [Display::new], but with the pixel depth `D`, e.g.
+    /// `Display::<Bpp1>::new_with_depth(...)`.
+    #[cfg(feature = "alloc")]
+    #[allow(clippy::type_complexity)]
+    pub fn new_with_depth<D0, D1, D2, D3, D4, D5, D6, D7, CfgData, CfgClk, CfgStr, Dc, Wrx, Rmt>(
+        pins: PinConfig<D0, D1, D2, D3, D4, D5, D6, D7, CfgData, CfgClk, CfgStr, Dc, Wrx, Rmt>,
+        dma: impl TxChannelFor<peripherals::LCD_CAM<'a>>,
+        lcd_cam: peripherals::LCD_CAM<'a>,
+        rmt: peripherals::RMT<'a>,
+    ) -> Result<Self>
+    where
+        D0: PeripheralOutput<'a>,
+        D1: PeripheralOutput<'a>,
+        D2: PeripheralOutput<'a>,
+        D3: PeripheralOutput<'a>,
+        D4: PeripheralOutput<'a>,
+        D5: PeripheralOutput<'a>,
+        D6: PeripheralOutput<'a>,
+        D7: PeripheralOutput<'a>,
+        CfgData: OutputPin + 'a,
+        CfgClk: OutputPin + 'a,
+        CfgStr: OutputPin + 'a,
+        Dc: PeripheralOutput<'a>,
+        Wrx: PeripheralOutput<'a>,
+        Rmt: OutputPin + 'a,
+    {
+        let epd = ed047tc1::ED047TC1::new(pins, dma, lcd_cam, rmt)?;
+        Ok(Self::from_parts(epd, Buffer::allocate(D::FRAMEBUFFER_SIZE)))
+    }
+
+    /// Same as [Display::new_with_framebuffer], but with the pixel depth
+    /// `D`. The framebuffer has to be [PixelDepth::FRAMEBUFFER_SIZE] bytes
+    /// long.
+    #[allow(clippy::type_complexity)]
+    pub fn new_with_depth_and_framebuffer<
+        D0,
+        D1,
+        D2,
+        D3,
+        D4,
+        D5,
+        D6,
+        D7,
+        CfgData,
+        CfgClk,
+        CfgStr,
+        Dc,
+        Wrx,
+        Rmt,
+    >(
+        pins: PinConfig<D0, D1, D2, D3, D4, D5, D6, D7, CfgData, CfgClk, CfgStr, Dc, Wrx, Rmt>,
+        framebuffer: &'a mut D::Framebuffer,
+        dma: impl TxChannelFor<peripherals::LCD_CAM<'a>>,
         lcd_cam: peripherals::LCD_CAM<'a>,
         rmt: peripherals::RMT<'a>,
-    ) -> Result<Self> {
-        Ok(Display {
-            epd: ed047tc1::ED047TC1::new(pins, dma, lcd_cam, rmt)?,
+    ) -> Result<Self>
+    where
+        D0: PeripheralOutput<'a>,
+        D1: PeripheralOutput<'a>,
+        D2: PeripheralOutput<'a>,
+        D3: PeripheralOutput<'a>,
+        D4: PeripheralOutput<'a>,
+        D5: PeripheralOutput<'a>,
+        D6: PeripheralOutput<'a>,
+        D7: PeripheralOutput<'a>,
+        CfgData: OutputPin + 'a,
+        CfgClk: OutputPin + 'a,
+        CfgStr: OutputPin + 'a,
+        Dc: PeripheralOutput<'a>,
+        Wrx: PeripheralOutput<'a>,
+        Rmt: OutputPin + 'a,
+    {
+        let epd = ed047tc1::ED047TC1::new(pins, dma, lcd_cam, rmt)?;
+        let framebuffer = framebuffer.as_mut();
+        framebuffer.fill(0xFF);
+        Ok(Self::from_parts(epd, Buffer::Borrowed(framebuffer)))
+    }
+
+    fn from_parts(epd: ed047tc1::ED047TC1<'a>, framebuffer: Buffer<'a>) -> Self {
+        Display {
+            epd,
             skipping: 0,
-            framebuffer: Box::new([0xFF; FRAMEBUFFER_SIZE]),
-            tainted_rows: [0; TAINTED_ROWS_SIZE],
-        })
+            framebuffer,
+            dirty_rows: DirtyRows::new(),
+            temperature_range: waveform::DEFAULT_TEMPERATURE_RANGE,
+            screen: None,
+            timings: Timings::DEFAULT,
+            clip: Some(Display::BOUNDING_BOX),
+            tone_curve: ToneCurve::DEFAULT,
+            depth: PhantomData,
+        }
+    }
+
+    /// Use the provided timings instead of [Timings::DEFAULT].
+    ///
+    /// Returns [Error::InvalidTimings] if the timings are out of range.
+    pub fn with_timings(mut self, timings: Timings) -> Result<Self> {
+        self.set_timings(timings)?;
+        Ok(self)
+    }
+
+    /// Set the timings used by [Display::flush] and [Display::clear].
+    ///
+    /// Returns [Error::InvalidTimings] if the timings are out of range.
+    pub fn set_timings(&mut self, timings: Timings) -> Result<()> {
+        timings.validate()?;
+        self.timings = timings;
+        Ok(())
+    }
+
+    /// Currently used timings.
+    pub fn timings(&self) -> &Timings {
+        &self.timings
+    }
+
+    /// Use the provided tone curve instead of [ToneCurve::DEFAULT].
+    pub fn with_tone_curve(mut self, tone_curve: ToneCurve) -> Self {
+        self.set_tone_curve(tone_curve);
+        self
+    }
+
+    /// Set the tone curve used to map 8-bit gray values to gray levels. The
+    /// curve only affects dithered input, see [tone](crate::tone).
+    pub fn set_tone_curve(&mut self, tone_curve: ToneCurve) {
+        self.tone_curve = tone_curve;
+    }
+
+    /// Currently used tone curve.
+    pub fn tone_curve(&self) -> &ToneCurve {
+        &self.tone_curve
     }
 
     /// Turn the display on.
     pub fn power_on(&mut self) {
-        debug!("Display power on");
+        debug!("display: power on");
         self.epd.power_on()
     }
 
     /// Turn the display off.
     pub fn power_off(&mut self) {
-        debug!("Display power off");
+        debug!("display: power off");
         self.epd.power_off()
     }
 
+    /// Set the current panel temperature in degrees celsius, e.g. as read by
+    /// [Tps65185::temperature](crate::Tps65185::temperature). The temperature
+    /// selects the waveform range used to scale the output times of
+    /// [Display::flush].
+    pub fn set_temperature(&mut self, temperature: i8) {
+        debug!("display: temperature {}", temperature);
+        self.temperature_range = waveform::temperature_range(temperature);
+    }
+
+    /// Restricts drawing to the provided area. Pixels outside of the clip
+    /// rectangle are discarded by [Display::set_pixel], [Display::fill] and
+    /// the `embedded-graphics` integration. The area is clamped to the
+    /// screen.
+    pub fn set_clip(&mut self, area: Rectangle) {
+        self.clip = area.intersection(&Display::BOUNDING_BOX);
+    }
+
+    /// Removes the clip rectangle, so the whole screen can be drawn again.
+    pub fn reset_clip(&mut self) {
+        self.clip = Some(Display::BOUNDING_BOX);
+    }
+
+    /// Returns the current clip rectangle, `None` if the clip rectangle
+    /// doesn't overlap the screen and nothing can be drawn.
+    pub fn clip(&self) -> Option<Rectangle> {
+        self.clip
+    }
+
     /// Sets a single pixel in the framebuffer without updating the display.
     ///
     /// If the provided coordinates are outside the screen, this method returns
-    /// [Error::OutOfBounds]. If the provided color is greater than 0x0F,
-    /// this method returns [Error::InvalidColor].
+    /// [Error::OutOfBounds]. If the provided color is greater than
+    /// [PixelDepth::MAX_COLOR] (0x0F for 4bpp), this method returns
+    /// [Error::InvalidColor]. Pixels outside of the
+    /// [clip rectangle](Display::set_clip) are ignored.
     pub fn set_pixel(&mut self, x: u16, y: u16, color: u8) -> Result<()> {
-        if x > Self::WIDTH || y > Self::HEIGHT {
-            return Err(Error::OutOfBounds);
-        }
-        if color > 0x0F {
-            return Err(Error::InvalidColor);
+        if framebuffer::set_pixel::<D>(&mut self.framebuffer, self.clip, x, y, color)? {
+            self.dirty_rows.insert(y);
         }
-        // Calculate the index in the framebuffer.
-        let index: usize = x as usize / 2 + y as usize * (Self::WIDTH as usize / 2);
-        let value = self.framebuffer[index];
-        if x % 2 == 1 {
-            self.framebuffer[index] = (value & 0x0F) | ((color << 4) & 0xF0);
-        } else {
-            self.framebuffer[index] = (value & 0xF0) | (color & 0x0F);
-        }
-        // taint row
-        let tainted_index = y as usize / TAINTED_ROWS_SIZE;
-        self.tainted_rows[tainted_index] |= 1 << ((y - (tainted_index as u16 * 8)) % 8);
         Ok(())
     }
 
-    /// Fill the whole framebuffer with the same color.
+    /// Fill the framebuffer with the same color. Only the
+    /// [clip rectangle](Display::set_clip) is filled, which covers the whole
+    /// framebuffer by default.
     pub fn fill(&mut self, color: u8) -> Result<()> {
-        debug!("display fill");
-        if color > 0x0F {
+        debug!("display: fill {}", color);
+        if color > D::MAX_COLOR {
             return Err(Error::InvalidColor);
         }
-        self.framebuffer.fill(color << 4 | color);
-        self.tainted_rows.fill(0xFF);
+        match self.clip {
+            Some(Display::BOUNDING_BOX) => {
+                // repeat the color for every pixel of a byte
+                self.framebuffer.fill(color * (0xFF / D::MAX_COLOR));
+                self.dirty_rows.insert_all();
+            }
+            Some(clip) => self.fill_area(clip, color),
+            None => {}
+        }
+        Ok(())
+    }
+
+    /// Fills the part of `area` inside the [clip rectangle](Display::set_clip)
+    /// with the same color.
+    ///
+    /// Returns [Error::InvalidColor] if the color is greater than
+    /// [PixelDepth::MAX_COLOR].
+    pub fn fill_rect(&mut self, area: Rectangle, color: u8) -> Result<()> {
+        let clip = self.clip;
+        if let Some(area) = framebuffer::fill_rect::<D>(&mut self.framebuffer, clip, area, color)? {
+            self.dirty_rows.insert_range(area.y..area.y + area.height);
+        }
         Ok(())
     }
 
+    /// Returns the framebuffer. Rows are [PixelDepth::LINE_BYTES] long, the
+    /// pixels are packed starting at the least significant bits, i.e. with
+    /// 4bpp the left pixel is stored in the lower nibble.
+    pub fn framebuffer(&self) -> &[u8] {
+        &self.framebuffer[..]
+    }
+
+    /// Returns the framebuffer for direct modification and marks all rows as
+    /// dirty. Use [Display::framebuffer_rows_mut] if only a few rows are
+    /// changed. The [clip rectangle](Display::set_clip) is not applied.
+    pub fn framebuffer_mut(&mut self) -> &mut [u8] {
+        self.dirty_rows.insert_all();
+        &mut self.framebuffer[..]
+    }
+
+    /// Returns the rows which have been modified since the last flush.
+    pub fn dirty_rows(&self) -> &DirtyRows {
+        &self.dirty_rows
+    }
+
+    /// Returns the provided rows of the framebuffer for direct modification
+    /// and marks them as dirty. The [clip rectangle](Display::set_clip) is
+    /// not applied.
+    ///
+    /// Returns [Error::OutOfBounds] if the rows exceed the screen.
+    pub fn framebuffer_rows_mut(&mut self, rows: Range<u16>) -> Result<&mut [u8]> {
+        if rows.start > rows.end || rows.end > Display::HEIGHT {
+            return Err(Error::OutOfBounds);
+        }
+        self.dirty_rows.insert_range(rows.clone());
+        let start = rows.start as usize * D::LINE_BYTES;
+        let end = rows.end as usize * D::LINE_BYTES;
+        Ok(&mut self.framebuffer[start..end])
+    }
+
     /// Flush updates the display with the contents of the framebuffer. The
     /// method clears the framebuffer. The provided mode should match the
     /// contents of your framebuffer.
     pub fn flush(&mut self, mode: DrawMode) -> Result<()> {
-        debug!("display flush");
-        self.draw(mode)?;
-        self.tainted_rows.fill(0);
+        let timings = self.timings;
+        self.flush_with_timings(mode, &timings)
+    }
+
+    /// Same as [Display::flush], but uses the provided timings for this update
+    /// only.
+    ///
+    /// Returns [Error::InvalidTimings] if the timings are out of range.
+    pub fn flush_with_timings(&mut self, mode: DrawMode, timings: &Timings) -> Result<()> {
+        timings.validate()?;
+        debug!("display: flush start {:?}", mode);
+        if self.dirty_rows.is_empty() {
+            debug!("display: flush skipped, nothing to draw");
+            return Ok(());
+        }
+        if let Err(err) = self.draw(mode, timings) {
+            self.recover(err);
+            return Err(err);
+        }
+        self.update_screen(mode);
+        self.dirty_rows.clear();
         self.framebuffer.fill(0xFF);
+        debug!("display: flush end");
         Ok(())
     }
 
-    /// Clears the screen.
+    /// Clears the screen using [ClearStrategy::Thorough].
     pub fn clear(&mut self) -> Result<()> {
-        debug!("display clear");
-        self.clear_area(Self::BOUNDING_BOX)
+        self.clear_with(ClearStrategy::Thorough)
+    }
+
+    /// Clears the screen using the provided strategy.
+    pub fn clear_with(&mut self, strategy: ClearStrategy) -> Result<()> {
+        self.clear_area_with(Display::BOUNDING_BOX, strategy)
     }
 
     /// Performs the screen repair routine as described here
     /// https://github.com/Xinyuan-LilyGO/LilyGo-EPD47/blob/master/examples/screen_repair/screen_repair.ino
-    pub fn repair(&mut self, delay: Delay) -> Result<()> {
-        debug!("display repair");
-        self.clear()?;
-        for _ in 0..20 {
-            self.push_pixels(Self::BOUNDING_BOX, 50, 0)?;
-            delay.delay_millis(500);
-        }
-        self.clear()?;
-        for _ in 0..40 {
-            self.push_pixels(Self::BOUNDING_BOX, 50, 1)?;
-            delay.delay_millis(500);
+    ///
+    /// Blocks until the routine has finished, use [Repair] to run it step by
+    /// step, e.g. from an async task.
+    pub fn repair(&mut self, config: RepairConfig, delay: &mut impl DelayNs) -> Result<()> {
+        debug!("display: repair start");
+        let mut repair = Repair::new(config);
+        while let RepairStatus::Wait(ms) = repair.step(self)? {
+            delay.delay_ms(ms);
         }
-        self.clear()
+        debug!("display: repair end");
+        Ok(())
     }
 
+    /// Clears the provided area of the screen using
+    /// [ClearStrategy::Thorough].
     pub fn clear_area(&mut self, area: Rectangle) -> Result<()> {
-        self.clear_cycles(area, 4, 50)
+        self.clear_area_with(area, ClearStrategy::Thorough)
     }
 
-    fn clear_cycles(&mut self, area: Rectangle, cycles: u16, cycle_time: u16) -> Result<()> {
-        for _ in 0..cycles {
-            for _ in 0..4 {
-                self.push_pixels(area, cycle_time, 0)?;
+    /// Clears the provided area of the screen using the provided strategy.
+    /// The area is clamped to the screen.
+    ///
+    /// The gray level of [ClearStrategy::Gray] is one of the 16 levels of the
+    /// panel for every pixel depth. With [Bpp2](crate::Bpp2) and
+    /// [Bpp1](crate::Bpp1) the [tracked](Display::track_screen) screen stores
+    /// the level rounded down to the depth (`level >> (4 - BITS)`), e.g.
+    /// 0x7 becomes 1 with 2bpp and 0 with 1bpp.
+    ///
+    /// Returns [Error::InvalidColor] if the gray level of
+    /// [ClearStrategy::Gray] is greater than 0x0F.
+    pub fn clear_area_with(&mut self, area: Rectangle, strategy: ClearStrategy) -> Result<()> {
+        debug!("display: clear {:?}", strategy);
+        let color = match strategy {
+            ClearStrategy::Gray(color) if color > 0x0F => return Err(Error::InvalidColor),
+            ClearStrategy::Gray(color) => color,
+            _ => 0x0F,
+        };
+        let Some(area) = area.intersection(&Display::BOUNDING_BOX) else {
+            return Ok(());
+        };
+        let result = self.push_clear(area, strategy);
+        if let Err(err) = result {
+            self.recover(err);
+            return result;
+        }
+        // quantize the gray level to the pixel depth
+        self.clear_screen_area(area, color >> (4 - D::BITS));
+        Ok(())
+    }
+
+    /// Encodes the framebuffer with the [codec] run-length encoding into `out`
+    /// and returns the number of bytes written.
+    ///
+    /// Returns [Error::BufferTooSmall] if `out` can't hold the encoded data.
+    pub fn encode_framebuffer(&self, out: &mut [u8]) -> Result<usize> {
+        codec::encode(&self.framebuffer[..], out)
+    }
+
+    /// Decodes data created by [Display::encode_framebuffer] or
+    /// [codec::encode] into the framebuffer and taints all rows.
+    ///
+    /// Returns [Error::InvalidEncoding] if the data is corrupted, the
+    /// framebuffer is reset to white in that case.
+    pub fn decode_framebuffer(&mut self, data: &[u8]) -> Result<()> {
+        self.dirty_rows.insert_all();
+        let result = codec::decode(data, &mut self.framebuffer[..]);
+        if result.is_err() {
+            self.framebuffer.fill(0xFF);
+        }
+        result
+    }
+
+    /// Writes the framebuffer as binary 8-bit PGM image (`P5`).
+    pub fn write_pgm<W: Write>(&self, writer: &mut W) -> core::result::Result<(), W::Error> {
+        screenshot::write_pgm::<D, W>(&self.framebuffer, writer)
+    }
+
+    /// Writes the framebuffer as grayscale PNG image with a bit depth matching
+    /// the pixel depth of the display.
+    pub fn write_png<W: Write>(&self, writer: &mut W) -> core::result::Result<(), W::Error> {
+        screenshot::write_png::<D, W>(&self.framebuffer, writer)
+    }
+
+    /// Draws the image read from `reader` with its top left corner at `x`,
+    /// `y` and returns the area covered by the image. Only the part inside
+    /// the [clip rectangle](Display::set_clip) is drawn, reading stops after
+    /// the last visible row.
+    ///
+    /// Gray and color images are dithered using `dithering`, error diffusion
+    /// keeps about 6kb of state on the stack.
+    ///
+    /// Returns [Error::UnsupportedFormat] if the image format is not
+    /// supported, [Error::InvalidEncoding] if the image is corrupted or the
+    /// stream ends early and [Error::Io] if reading fails. The image might be
+    /// partially drawn in that case.
+    pub fn draw_stream<R: Read>(
+        &mut self,
+        reader: &mut R,
+        x: u16,
+        y: u16,
+        dithering: Dithering,
+    ) -> Result<Rectangle> {
+        let mut stream = Stream::new(reader)?;
+        let area = stream.area(x, y);
+        let Some(target) = self.clip.and_then(|clip| clip.intersection(&area)) else {
+            return Ok(area);
+        };
+        debug!("display: draw stream {}x{}", area.width, area.height);
+        self.dirty_rows
+            .insert_range(target.y..target.y + target.height);
+        let curve = self.tone_curve;
+        stream.draw::<D>(&mut self.framebuffer, x, y, target, dithering, &curve)?;
+        Ok(area)
+    }
+
+    /// Start tracking the image shown on the panel. The tracked image is
+    /// updated by [Display::flush] and [Display::clear_area] and can be
+    /// persisted with [Display::snapshot], e.g. before entering deep sleep.
+    ///
+    /// The panel is assumed to be white when tracking starts, so this should
+    /// be called right after [Display::clear]. The tracked image requires
+    /// another framebuffer worth of memory, which is allocated on the heap.
+    #[cfg(feature = "alloc")]
+    pub fn track_screen(&mut self) {
+        if self.screen.is_none() {
+            self.screen = Some(Buffer::allocate(D::FRAMEBUFFER_SIZE));
+        }
+    }
+
+    /// Same as [Display::track_screen], but stores the tracked image in the
+    /// provided memory. Replaces a previously tracked image.
+    pub fn track_screen_with(&mut self, screen: &'a mut D::Framebuffer) {
+        let screen = screen.as_mut();
+        screen.fill(0xFF);
+        self.screen = Some(Buffer::Borrowed(screen));
+    }
+
+    /// Writes a compressed snapshot of the tracked image to `out` and returns
+    /// the number of bytes written. Mostly white screens compress to a few
+    /// hundred bytes, which fit into RTC memory.
+    ///
+    /// Returns [Error::ScreenNotTracked] if [Display::track_screen] has not
+    /// been called and [Error::BufferTooSmall] if `out` can't hold the
+    /// snapshot.
+    pub fn snapshot(&self, out: &mut [u8]) -> Result<usize> {
+        let screen = self.screen.as_ref().ok_or(Error::ScreenNotTracked)?;
+        let header = out
+            .get_mut(..SNAPSHOT_HEADER.len())
+            .ok_or(Error::BufferTooSmall)?;
+        header.copy_from_slice(&SNAPSHOT_HEADER);
+        let len = codec::encode(&screen[..], &mut out[SNAPSHOT_HEADER.len()..])?;
+        debug!("display: snapshot {} bytes", len + SNAPSHOT_HEADER.len());
+        Ok(len + SNAPSHOT_HEADER.len())
+    }
+
+    /// Restores the tracked image from a snapshot created by
+    /// [Display::snapshot], e.g. after waking up from deep sleep. Screen
+    /// tracking is enabled if necessary.
+    ///
+    /// Returns [Error::InvalidEncoding] if the snapshot is corrupted. Without
+    /// the `alloc` feature, [Error::ScreenNotTracked] is returned unless
+    /// [Display::track_screen_with] has been called before.
+    pub fn restore(&mut self, snapshot: &[u8]) -> Result<()> {
+        if !snapshot.starts_with(&SNAPSHOT_HEADER) {
+            return Err(Error::InvalidEncoding);
+        }
+        let mut screen = match self.screen.take() {
+            Some(screen) => screen,
+            #[cfg(feature = "alloc")]
+            None => Buffer::allocate(D::FRAMEBUFFER_SIZE),
+            #[cfg(not(feature = "alloc"))]
+            None => return Err(Error::ScreenNotTracked),
+        };
+        let result = codec::decode(&snapshot[SNAPSHOT_HEADER.len()..], &mut screen[..]);
+        if result.is_err() {
+            screen.fill(0xFF);
+        }
+        self.screen = Some(screen);
+        debug!("display: restore {} bytes", snapshot.len());
+        result
+    }
+
+    /// Returns the bounding box of all non-white pixels of the tracked image
+    /// or `None` if the tracked image is blank or screen tracking is disabled.
+    pub fn screen_bounding_box(&self) -> Option<Rectangle> {
+        let screen = self.screen.as_ref()?;
+        let (mut min_x, mut min_y, mut max_x, mut max_y) = (usize::MAX, usize::MAX, 0, 0);
+        // positions of the non-white pixels within a byte
+        let colored = |byte: u8| {
+            (0..D::PIXELS_PER_BYTE)
+                .filter(move |p| (byte >> (p * D::BITS)) & D::MAX_COLOR != D::MAX_COLOR)
+        };
+        for (y, row) in screen.chunks_exact(D::LINE_BYTES).enumerate() {
+            let Some(first) = row.iter().position(|&b| b != 0xFF) else {
+                continue;
+            };
+            let last = row.iter().rposition(|&b| b != 0xFF).unwrap_or(first);
+            let first_x = first * D::PIXELS_PER_BYTE + colored(row[first]).next().unwrap_or(0);
+            let last_x = last * D::PIXELS_PER_BYTE + colored(row[last]).next_back().unwrap_or(0);
+            min_x = min_x.min(first_x);
+            max_x = max_x.max(last_x);
+            min_y = min_y.min(y);
+            max_y = max_y.max(y);
+        }
+        if min_y == usize::MAX {
+            return None;
+        }
+        Some(Rectangle {
+            x: min_x as u16,
+            y: min_y as u16,
+            width: (max_x - min_x + 1) as u16,
+            height: (max_y - min_y + 1) as u16,
+        })
+    }
+
+    fn update_screen(&mut self, mode: DrawMode) {
+        let Some(screen) = self.screen.as_deref_mut() else {
+            return;
+        };
+        for y in self.dirty_rows.iter() {
+            let start = y as usize * D::LINE_BYTES;
+            let end = start + D::LINE_BYTES;
+            for (pixels, &update) in screen[start..end]
+                .iter_mut()
+                .zip(&self.framebuffer[start..end])
+            {
+                *pixels = match mode {
+                    // untouched (white) pixels are not driven, pixels only get
+                    // darker
+                    DrawMode::BlackOnWhite => combine_pixels::<D>(*pixels, update, u8::min),
+                    // all pixels of the row are driven towards white
+                    DrawMode::WhiteOnBlack => combine_pixels::<D>(*pixels, update, u8::max),
+                    DrawMode::WhiteOnWhite => update,
+                };
+            }
+        }
+    }
+
+    fn clear_screen_area(&mut self, area: Rectangle, color: u8) {
+        let Some(screen) = self.screen.as_deref_mut() else {
+            return;
+        };
+        let x_end = (area.x as usize + area.width as usize).min(Display::WIDTH as usize);
+        let y_end = (area.y as usize + area.height as usize).min(Display::HEIGHT as usize);
+        for y in area.y as usize..y_end {
+            for x in area.x as usize..x_end {
+                write_pixel::<D>(screen, x, y, color);
+            }
+        }
+    }
+
+    fn push_clear(&mut self, area: Rectangle, strategy: ClearStrategy) -> Result<()> {
+        let cycles = self.timings.clear_cycles;
+        let time = self.timings.clear_cycle_time;
+        match strategy {
+            ClearStrategy::Fast => self.push_cycles(area, 1, time, &[(BLACK, 4), (WHITE, 4)])?,
+            ClearStrategy::Thorough | ClearStrategy::Gray(_) => {
+                self.push_cycles(area, cycles, time, &[(BLACK, 4), (WHITE, 4)])?
+            }
+            ClearStrategy::InvertedFlash => {
+                self.push_cycles(area, cycles, time, &[(WHITE, 2), (BLACK, 4), (WHITE, 2)])?
+            }
+        }
+        if let ClearStrategy::Gray(color) = strategy {
+            // darken the white area frame by frame, like a black on white flush
+            let frames = Timings::MAX_FRAME_COUNT as usize - color as usize;
+            for k in 0..frames {
+                let time =
+                    waveform::scale_time(self.timings.contrast_cycles[k], self.temperature_range);
+                self.push_pixels(area, time, BLACK)?;
             }
-            for _ in 0..4 {
-                self.push_pixels(area, cycle_time, 1)?;
+        }
+        Ok(())
+    }
+
+    /// Pushes the `(color, count)` sequence `cycles` times.
+    fn push_cycles(
+        &mut self,
+        area: Rectangle,
+        cycles: u16,
+        cycle_time: u16,
+        sequence: &[(u16, u16)],
+    ) -> Result<()> {
+        for _ in 0..cycles {
+            for &(color, count) in sequence {
+                for _ in 0..count {
+                    self.push_pixels(area, cycle_time, color)?;
+                }
             }
         }
         Ok(())
     }
 
+    /// Pushes all pixels of `area` towards `color` for a single frame and
+    /// recovers from transfer errors.
+    pub(crate) fn push_frame(&mut self, area: Rectangle, time: u16, color: u16) -> Result<()> {
+        let result = self.push_pixels(area, time, color);
+        if let Err(err) = result {
+            self.recover(err);
+        }
+        result
+    }
+
     fn push_pixels(&mut self, area: Rectangle, time: u16, color: u16) -> Result<()> {
         let mut row = [0u8; BYTES_PER_LINE];
 
         for i in 0..area.width {
             let pos = i + area.x % 4;
             let mask = match color {
-                1 => 0b10101010,
+                WHITE => 0b10101010,
                 _ => 0b01010101,
             } & (0b00000011 << (2 * (pos % 4)));
             row[(area.x / 4 + pos / 4) as usize] |= mask;
@@ -193,7 +896,7 @@ impl<'a> Display<'a> {
         line_buffer_reorder(&mut row);
         self.epd.frame_start()?;
 
-        for i in 0..Self::WIDTH {
+        for i in 0..Display::WIDTH {
             // before are of interest: skip
             if i < area.y {
                 self.row_skip(time)?;
@@ -216,6 +919,12 @@ impl<'a> Display<'a> {
         Ok(())
     }
 
+    /// Fills an area inside the screen and marks its rows as dirty.
+    fn fill_area(&mut self, area: Rectangle, color: u8) {
+        framebuffer::fill_area::<D>(&mut self.framebuffer, area, color);
+        self.dirty_rows.insert_range(area.y..area.y + area.height);
+    }
+
     fn row_skip(&mut self, output_time: u16) -> Result<()> {
         match self.skipping {
             0 => {
@@ -241,49 +950,81 @@ impl<'a> Display<'a> {
         Ok(())
     }
 
-    fn is_tainted(&self, row: u16) -> bool {
-        let index = row as usize / TAINTED_ROWS_SIZE;
-        self.tainted_rows[index] & (1 << ((row - (index as u16 * 8)) % 8)) != 0
+    /// Ends a frame which has been interrupted by an error, so the next
+    /// update starts from a clean state.
+    fn recover(&mut self, err: Error) {
+        error!("display: transfer failed, recovering: {}", err);
+        self.skipping = 0;
+        let _ = self.epd.frame_end();
     }
 
-    const DRAW_IMAGE_FRAME_COUNT: usize = 15;
-    fn draw(&mut self, mode: DrawMode) -> Result<()> {
-        // let start = esp_hal::time::current_time();
-
-        // init lut
-        let mut lut = vec![mode.lut_default(); 1 << 16];
+    /// Draws the framebuffer. With 4bpp every frame drives the pixels
+    /// towards a single gray level, lower depths combine the frames between
+    /// their gray levels into a single, longer frame.
+    fn draw(&mut self, mode: DrawMode, timings: &Timings) -> Result<()> {
+        let mut lut = [0u16; 256];
+        let mut buf = [0u8; BYTES_PER_LINE];
+        let frames_per_level = DRAW_IMAGE_FRAME_COUNT / D::MAX_COLOR as usize;
 
-        for k in 0..Self::DRAW_IMAGE_FRAME_COUNT {
+        for frame in 0..D::MAX_COLOR as usize {
+            let start = frame * frames_per_level;
+            let end = (start + frames_per_level).min(timings.frame_count as usize);
+            if start >= end {
+                break;
+            }
+            let time = mode.contrast_cycles(timings)[start..end]
+                .iter()
+                .map(|&time| waveform::scale_time(time, self.temperature_range))
+                .fold(0u16, u16::saturating_add);
             // update lut
-            update_lut(&mut lut, k, mode);
+            update_lut::<D>(&mut lut, frame, mode);
             // start draw
             self.epd.frame_start()?;
             // build line
-            for y in 0..Self::HEIGHT {
-                if !self.is_tainted(y) {
+            for y in 0..Display::HEIGHT {
+                if !self.dirty_rows.contains(y) {
                     self.epd.skip()?;
                     continue;
                 }
-                let start = y as usize * LINE_BYTES_4BPP;
-                let end = start + LINE_BYTES_4BPP;
+                let start = y as usize * D::LINE_BYTES;
+                let end = start + D::LINE_BYTES;
                 // draw
-                let buf = prepare_dma_buffer(&self.framebuffer[start..end], &lut);
-                self.epd.set_buffer(buf.as_slice())?;
-                self.epd.output_row(mode.contrast_cycles()[k])?;
+                prepare_dma_buffer::<D>(&self.framebuffer[start..end], &lut, &mut buf);
+                self.epd.set_buffer(&buf)?;
+                self.epd.output_row(time)?;
             }
             if self.skipping == 0 {
-                self.row_write(mode.contrast_cycles()[k])?;
+                self.row_write(time)?;
             }
             self.epd.frame_end()?;
         }
-        // println!(
-        //     "draw_fb {}",
-        //     (esp_hal::time::current_time() - start).to_millis()
-        // );
         Ok(())
     }
 }
 
+impl Repair {
+    /// Performs the next step of the routine.
+    ///
+    /// Returns [RepairStatus::Wait] as long as the routine is in progress.
+    /// Calling this method after the routine has finished has no effect.
+    pub fn step<D: PixelDepth>(&mut self, display: &mut Display<'_, D>) -> Result<RepairStatus> {
+        self.advance(|action| match action {
+            Action::Clear => display.clear(),
+            Action::Black(time) => display.push_frame(Display::BOUNDING_BOX, time, BLACK),
+            Action::White(time) => display.push_frame(Display::BOUNDING_BOX, time, WHITE),
+        })
+    }
+}
+
+/// Combines every pixel of `a` with the corresponding pixel of `b`.
+fn combine_pixels<D: PixelDepth>(a: u8, b: u8, combine: impl Fn(u8, u8) -> u8) -> u8 {
+    (0..D::PIXELS_PER_BYTE).fold(0, |result, p| {
+        let shift = p * D::BITS;
+        let pixel = combine((a >> shift) & D::MAX_COLOR, (b >> shift) & D::MAX_COLOR);
+        result | pixel << shift
+    })
+}
+
 fn line_buffer_reorder(data: &mut [u8]) {
     // Iterate over the data in chunks of 4 bytes (size of a u32)
     for chunk in data.chunks_exact_mut(4) {
@@ -295,53 +1036,48 @@ fn line_buffer_reorder(data: &mut [u8]) {
     }
 }
 
-fn prepare_dma_buffer(line_data: &[u8], conversion_lut: &[u8]) -> Vec<u8> {
-    let mut epd_input = vec![0u8; BYTES_PER_LINE];
-    let mut wide_epd_input: Vec<u32> = vec![0u32; Display::WIDTH as usize / 16];
-
-    let line_data_16: Vec<u16> = line_data
-        .chunks(2)
-        .map(|chunk| u16::from_le_bytes([chunk[0], chunk[1]]))
-        .collect();
-
-    for (j, chunk) in line_data_16.chunks(4).enumerate() {
-        if let [v1, v2, v3, v4] = chunk {
-            let pixel: u32 = (conversion_lut[*v1 as usize] as u32)
-                | (conversion_lut[*v2 as usize] as u32) << 8
-                | (conversion_lut[*v3 as usize] as u32) << 16
-                | (conversion_lut[*v4 as usize] as u32) << 24;
-            wide_epd_input[j] = pixel;
+/// Converts a row of the framebuffer into the 2 bits per pixel driving the
+/// panel, see [update_lut].
+fn prepare_dma_buffer<D: PixelDepth>(
+    line_data: &[u8],
+    lut: &[u16; 256],
+    epd_input: &mut [u8; BYTES_PER_LINE],
+) {
+    let mut bits = 0u32;
+    let mut count = 0;
+    let mut out = epd_input.iter_mut();
+    for &pixels in line_data {
+        bits |= (lut[pixels as usize] as u32) << count;
+        count += 2 * D::PIXELS_PER_BYTE;
+        while count >= 8 {
+            match out.next() {
+                Some(byte) => *byte = bits as u8,
+                None => return,
+            }
+            bits >>= 8;
+            count -= 8;
         }
     }
-
-    for (i, &wide_pixel) in wide_epd_input.iter().enumerate() {
-        epd_input[i * 4..(i + 1) * 4].copy_from_slice(&wide_pixel.to_le_bytes());
-    }
-
-    epd_input
 }
 
-fn update_lut(conversion_lut: &mut [u8], k: usize, mode: DrawMode) {
-    let k = match mode {
-        DrawMode::BlackOnWhite | DrawMode::WhiteOnWhite => Display::DRAW_IMAGE_FRAME_COUNT - k,
-        DrawMode::WhiteOnBlack => k,
+/// Updates the lookup table for `frame`. The table maps a byte of the
+/// framebuffer to the two bits driving each of its pixels. A pixel is driven
+/// as long as the frame hasn't reached its gray level.
+fn update_lut<D: PixelDepth>(lut: &mut [u16; 256], frame: usize, mode: DrawMode) {
+    let drive = (mode.lut_default() & 0x03) as u16;
+    let pixel = |value: usize| {
+        let driven = match mode {
+            DrawMode::BlackOnWhite | DrawMode::WhiteOnWhite => {
+                value + frame < D::MAX_COLOR as usize
+            }
+            DrawMode::WhiteOnBlack => value > frame,
+        };
+        driven as u16 * drive
     };
-    // reset the pixels which are not to be lightened / darkened
-    // any longer in the current frame
-    for l in (k..1 << 16).step_by(16) {
-        conversion_lut[l] &= 0xFC;
-    }
-    for l in ((k << 4)..(1 << 16)).step_by(1 << 8) {
-        for p in 0..16 {
-            conversion_lut[l + p] &= 0xF3
-        }
-    }
-    for l in ((k << 8)..(1 << 16)).step_by(1 << 12) {
-        for p in 0..(1 << 8) {
-            conversion_lut[l + p] &= 0xCF
-        }
-    }
-    for l in (k << 12)..((k + 1) << 12) {
-        conversion_lut[l] &= 0x3F;
+    for (pixels, entry) in lut.iter_mut().enumerate() {
+        *entry = (0..D::PIXELS_PER_BYTE).fold(0, |bits, p| {
+            let value = (pixels >> (p * D::BITS)) & D::MAX_COLOR as usize;
+            bits | pixel(value) << (2 * p)
+        });
     }
 }
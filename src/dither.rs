@@ -0,0 +1,239 @@
+//! Dithering of 8-bit grayscale and RGB input.
+//!
+//! [DitheringDrawTarget] (`embedded-graphics` feature) wraps a
+//! [Display](crate::Display) and quantises any color that can be converted
+//! into [Gray8] (e.g. [Gray8], `Rgb565` or `Rgb888`) to the gray levels of the
+//! display, i.e. 16 levels for [Bpp4](crate::Bpp4) and 2 levels for
+//! [Bpp1](crate::Bpp1). The levels are picked according to the [ToneCurve] of
+//! the display. [Display::draw_stream](crate::Display::draw_stream) uses the
+//! same algorithms for images read from a stream.
+//!
+//! ```rust ignore
+//! let mut target = DitheringDrawTarget::new(&mut display, Dithering::FloydSteinberg);
+//! Image::new(&photo, Point::zero()).draw(&mut target)?;
+//! ```
+
+#[cfg(feature = "embedded-graphics")]
+use core::marker::PhantomData;
+
+#[cfg(feature = "embedded-graphics")]
+use embedded_graphics_core::{
+    pixelcolor::{Gray8, GrayColor},
+    prelude::*,
+};
+
+use crate::{depth::PixelDepth, framebuffer, tone::ToneCurve};
+#[cfg(feature = "embedded-graphics")]
+use crate::{display::Display, Error};
+
+const WIDTH: usize = framebuffer::WIDTH as usize;
+
+/// Rows of diffusion error kept by [DitheringDrawTarget].
+const ERROR_ROWS: usize = 3;
+
+/// 4x4 Bayer threshold matrix.
+const BAYER: [[u8; 4]; 4] = [[0, 8, 2, 10], [12, 4, 14, 6], [3, 11, 1, 9], [15, 7, 13, 5]];
+
+/// Dithering algorithm.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Dithering {
+    /// Ordered dithering using a 4x4 Bayer matrix. Stateless, so pixels can be
+    /// drawn in any order, but produces a visible cross-hatch pattern.
+    Bayer,
+    /// Floyd–Steinberg error diffusion, distributes the whole quantisation
+    /// error to the neighbouring pixels.
+    FloydSteinberg,
+    /// Atkinson error diffusion, distributes 3/4 of the quantisation error
+    /// which results in more contrast but loses detail in very dark and very
+    /// light areas.
+    Atkinson,
+}
+
+/// [DrawTarget] which dithers colors to the gray levels of the wrapped
+/// [Display].
+///
+/// Error diffusion expects the pixels of a draw call in row-major order, like
+/// images and filled shapes are drawn by `embedded-graphics`. The error is
+/// reset at the start of every draw call and whenever a pixel above the
+/// current row is drawn. The diffusion error takes about 6kb, so consider
+/// keeping the target in a `static` on devices with a small stack.
+#[cfg(feature = "embedded-graphics")]
+pub struct DitheringDrawTarget<'d, 'a, D: PixelDepth, C> {
+    display: &'d mut Display<'a, D>,
+    ditherer: Ditherer,
+    color: PhantomData<C>,
+}
+
+#[cfg(feature = "embedded-graphics")]
+impl<'d, 'a, D: PixelDepth, C> DitheringDrawTarget<'d, 'a, D, C>
+where
+    C: PixelColor + Into<Gray8>,
+{
+    /// Wraps the display.
+    pub fn new(display: &'d mut Display<'a, D>, dithering: Dithering) -> Self {
+        DitheringDrawTarget {
+            display,
+            ditherer: Ditherer::new(dithering),
+            color: PhantomData,
+        }
+    }
+
+    /// Returns the dithering algorithm.
+    pub fn dithering(&self) -> Dithering {
+        self.ditherer.dithering
+    }
+
+    /// Changes the dithering algorithm.
+    pub fn set_dithering(&mut self, dithering: Dithering) {
+        self.ditherer.dithering = dithering;
+        self.ditherer.reset();
+    }
+
+    /// Returns the wrapped display.
+    pub fn display(&mut self) -> &mut Display<'a, D> {
+        self.display
+    }
+
+    /// Discards the diffusion error.
+    pub fn reset(&mut self) {
+        self.ditherer.reset();
+    }
+}
+
+/// Quantisation state shared by [DitheringDrawTarget] and
+/// [Display::draw_stream](crate::Display::draw_stream).
+pub(crate) struct Ditherer {
+    dithering: Dithering,
+    errors: [[i16; WIDTH]; ERROR_ROWS],
+    row: Option<u16>,
+}
+
+impl Ditherer {
+    pub(crate) fn new(dithering: Dithering) -> Self {
+        Ditherer {
+            dithering,
+            errors: [[0; WIDTH]; ERROR_ROWS],
+            row: None,
+        }
+    }
+
+    /// Discards the diffusion error.
+    pub(crate) fn reset(&mut self) {
+        self.errors = [[0; WIDTH]; ERROR_ROWS];
+        self.row = None;
+    }
+
+    /// Quantises an 8-bit gray value at the given position to a pixel value
+    /// of the depth `D`.
+    pub(crate) fn quantise<D: PixelDepth>(
+        &mut self,
+        x: u16,
+        y: u16,
+        luma: u8,
+        curve: &ToneCurve,
+    ) -> u8 {
+        let target = curve.target(luma as i32);
+        match self.dithering {
+            Dithering::Bayer => {
+                let (value, position) = curve.bracket::<D>(target);
+                value + (position > bayer_threshold(x, y)) as u8
+            }
+            Dithering::FloydSteinberg | Dithering::Atkinson => {
+                self.advance_to(y);
+                let x = x as usize;
+                let row = y as usize % ERROR_ROWS;
+                let (black, white) = (curve.lightness::<D>(0), curve.lightness::<D>(D::MAX_COLOR));
+                let lightness = (target + self.errors[row][x] as i32).clamp(black, white);
+                let value = curve.nearest::<D>(lightness);
+                self.diffuse(x, y as usize, lightness - curve.lightness::<D>(value));
+                value
+            }
+        }
+    }
+
+    /// Moves the diffusion error to row `y`.
+    fn advance_to(&mut self, y: u16) {
+        match self.row {
+            Some(row) if y < row => self.reset(),
+            Some(row) => {
+                // rows which have been left are reused for the rows below
+                for row in row..y.min(row + ERROR_ROWS as u16) {
+                    self.errors[row as usize % ERROR_ROWS].fill(0);
+                }
+            }
+            None => {}
+        }
+        self.row = Some(y);
+    }
+
+    /// Distributes the quantisation error of the pixel at `x`, `y`.
+    fn diffuse(&mut self, x: usize, y: usize, error: i32) {
+        let dithering = self.dithering;
+        let mut add = |dx: isize, dy: usize, part: i32| {
+            let Some(x) = x.checked_add_signed(dx).filter(|&x| x < WIDTH) else {
+                return;
+            };
+            let error = &mut self.errors[(y + dy) % ERROR_ROWS][x];
+            *error = (*error as i32 + part).clamp(i16::MIN as i32, i16::MAX as i32) as i16;
+        };
+        match dithering {
+            Dithering::FloydSteinberg => {
+                add(1, 0, error * 7 / 16);
+                add(-1, 1, error * 3 / 16);
+                add(0, 1, error * 5 / 16);
+                add(1, 1, error / 16);
+            }
+            Dithering::Atkinson => {
+                let part = error / 8;
+                add(1, 0, part);
+                add(2, 0, part);
+                add(-1, 1, part);
+                add(0, 1, part);
+                add(1, 1, part);
+                add(0, 2, part);
+            }
+            Dithering::Bayer => {}
+        }
+    }
+}
+
+/// Ordered dithering threshold of a pixel, scaled to 0 - 255.
+pub(crate) fn bayer_threshold(x: u16, y: u16) -> i32 {
+    (BAYER[y as usize % 4][x as usize % 4] as i32 * 2 + 1) * 255 / 32
+}
+
+#[cfg(feature = "embedded-graphics")]
+impl<D: PixelDepth, C> DrawTarget for DitheringDrawTarget<'_, '_, D, C>
+where
+    C: PixelColor + Into<Gray8>,
+{
+    type Color = C;
+
+    type Error = Error;
+
+    fn draw_iter<I>(&mut self, pixels: I) -> Result<(), Self::Error>
+    where
+        I: IntoIterator<Item = Pixel<Self::Color>>,
+    {
+        self.reset();
+        let curve = *self.display.tone_curve();
+        for Pixel(coord, color) in pixels.into_iter() {
+            let Some((x, y)) = framebuffer::screen_point(coord.x, coord.y) else {
+                continue;
+            };
+            let level = self
+                .ditherer
+                .quantise::<D>(x, y, color.into().luma(), &curve);
+            self.display.set_pixel(x, y, level)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(feature = "embedded-graphics")]
+impl<D: PixelDepth, C> OriginDimensions for DitheringDrawTarget<'_, '_, D, C> {
+    fn size(&self) -> Size {
+        Size::new(Display::WIDTH as u32, Display::HEIGHT as u32)
+    }
+}
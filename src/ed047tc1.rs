@@ -1,7 +1,7 @@
 use esp_hal::{
-    dma::DmaTxBuf,
+    dma::{DmaTxBuf, TxChannelFor},
     dma_buffers,
-    gpio::{Level, Output, OutputConfig, OutputPin},
+    gpio::{interconnect::PeripheralOutput, Level, Output, OutputConfig, OutputPin, Pin},
     lcd_cam::{
         lcd::{i8080, i8080::Command},
         LcdCam,
@@ -81,23 +81,46 @@ impl<'a> ConfigWriter<'a> {
     }
 }
 
-pub struct PinConfig<'a> {
-    pub data0: peripherals::GPIO8<'a>,
-    pub data1: peripherals::GPIO1<'a>,
-    pub data2: peripherals::GPIO2<'a>,
-    pub data3: peripherals::GPIO3<'a>,
-    pub data4: peripherals::GPIO4<'a>,
-    pub data5: peripherals::GPIO5<'a>,
-    pub data6: peripherals::GPIO6<'a>,
-    pub data7: peripherals::GPIO7<'a>,
-    pub cfg_data: peripherals::GPIO13<'a>,
-    pub cfg_clk: peripherals::GPIO12<'a>,
-    pub cfg_str: peripherals::GPIO0<'a>,
-    pub lcd_dc: peripherals::GPIO40<'a>,
-    pub lcd_wrx: peripherals::GPIO41<'a>,
-    pub rmt: peripherals::GPIO38<'a>,
+/// Pins used to drive the panel.
+///
+/// The pin types are generic so that boards which route the panel to other
+/// ESP32-S3 pins can reuse the driver. Use [PinConfigV23] together with the
+/// [pin_config](crate::pin_config) macro for the LilyGo T5 V2.3 wiring.
+pub struct PinConfig<D0, D1, D2, D3, D4, D5, D6, D7, CfgData, CfgClk, CfgStr, Dc, Wrx, Rmt> {
+    pub data0: D0,
+    pub data1: D1,
+    pub data2: D2,
+    pub data3: D3,
+    pub data4: D4,
+    pub data5: D5,
+    pub data6: D6,
+    pub data7: D7,
+    pub cfg_data: CfgData,
+    pub cfg_clk: CfgClk,
+    pub cfg_str: CfgStr,
+    pub lcd_dc: Dc,
+    pub lcd_wrx: Wrx,
+    pub rmt: Rmt,
 }
 
+/// Pin configuration of the LilyGo T5 4.7 inch V2.3 board.
+pub type PinConfigV23<'a> = PinConfig<
+    peripherals::GPIO8<'a>,
+    peripherals::GPIO1<'a>,
+    peripherals::GPIO2<'a>,
+    peripherals::GPIO3<'a>,
+    peripherals::GPIO4<'a>,
+    peripherals::GPIO5<'a>,
+    peripherals::GPIO6<'a>,
+    peripherals::GPIO7<'a>,
+    peripherals::GPIO13<'a>,
+    peripherals::GPIO12<'a>,
+    peripherals::GPIO0<'a>,
+    peripherals::GPIO40<'a>,
+    peripherals::GPIO41<'a>,
+    peripherals::GPIO38<'a>,
+>;
+
 pub(crate) struct ED047TC1<'a> {
     i8080: Option<i8080::I8080<'a, Blocking>>,
     cfg_writer: ConfigWriter<'a>,
@@ -106,12 +129,29 @@ pub(crate) struct ED047TC1<'a> {
 }
 
 impl<'a> ED047TC1<'a> {
-    pub(crate) fn new(
-        pins: PinConfig<'a>,
-        dma: peripherals::DMA_CH0<'a>,
+    #[allow(clippy::type_complexity)]
+    pub(crate) fn new<D0, D1, D2, D3, D4, D5, D6, D7, CfgData, CfgClk, CfgStr, Dc, Wrx, Rmt>(
+        pins: PinConfig<D0, D1, D2, D3, D4, D5, D6, D7, CfgData, CfgClk, CfgStr, Dc, Wrx, Rmt>,
+        dma: impl TxChannelFor<peripherals::LCD_CAM<'a>>,
         lcd_cam: peripherals::LCD_CAM<'a>,
         rmt: peripherals::RMT<'a>,
-    ) -> crate::Result<Self> {
+    ) -> crate::Result<Self>
+    where
+        D0: PeripheralOutput<'a>,
+        D1: PeripheralOutput<'a>,
+        D2: PeripheralOutput<'a>,
+        D3: PeripheralOutput<'a>,
+        D4: PeripheralOutput<'a>,
+        D5: PeripheralOutput<'a>,
+        D6: PeripheralOutput<'a>,
+        D7: PeripheralOutput<'a>,
+        CfgData: OutputPin + 'a,
+        CfgClk: OutputPin + 'a,
+        CfgStr: OutputPin + 'a,
+        Dc: PeripheralOutput<'a>,
+        Wrx: PeripheralOutput<'a>,
+        Rmt: OutputPin + 'a,
+    {
         // init lcd
         let lcd_cam = LcdCam::new(lcd_cam);
 
@@ -131,7 +171,7 @@ impl<'a> ED047TC1<'a> {
         let ctrl = ED047TC1 {
             i8080: Some(
                 i8080::I8080::new(lcd_cam.lcd, dma, config)
-                    .expect("Unable to create i8080")
+                    .map_err(crate::Error::I8080Config)?
                     .with_dc(pins.lcd_dc)
                     .with_wrx(pins.lcd_wrx)
                     .with_data0(pins.data0)
@@ -144,7 +184,7 @@ impl<'a> ED047TC1<'a> {
                     .with_data7(pins.data7),
             ),
             cfg_writer,
-            rmt: rmt::Rmt::new(rmt),
+            rmt: rmt::Rmt::new(rmt, pins.rmt.degrade()),
             dma_buf,
         };
         Ok(ctrl)
@@ -216,8 +256,14 @@ impl<'a> ED047TC1<'a> {
     pub(crate) fn output_row(&mut self, output_time: u16) -> crate::Result<()> {
         self.latch_row();
         self.rmt.pulse(output_time, 50, false)?;
-        let i8080 = self.i8080.take().ok_or(crate::Error::Unknown)?;
-        let dma_buf = self.dma_buf.take().ok_or(crate::Error::Unknown)?;
+        let i8080 = self.i8080.take().ok_or(crate::Error::BusLost)?;
+        let dma_buf = match self.dma_buf.take() {
+            Some(dma_buf) => dma_buf,
+            None => {
+                self.i8080 = Some(i8080);
+                return Err(crate::Error::BusLost);
+            }
+        };
         let tx = i8080
             .send(Command::<u8>::One(0), 0, dma_buf)
             .map_err(|(err, i8080, buf)| {
@@ -226,11 +272,12 @@ impl<'a> ED047TC1<'a> {
                 crate::Error::Dma(err)
             })?;
         let (r, i8080, dma_buf) = tx.wait();
-        r.map_err(crate::Error::Dma)?;
+        // restore the bus before reporting errors, so the next transfer can
+        // proceed
         self.i8080 = Some(i8080);
         self.dma_buf = Some(dma_buf);
 
-        Ok(())
+        r.map_err(crate::Error::Dma)
     }
 
     pub(crate) fn frame_end(&mut self) -> crate::Result<()> {
@@ -245,10 +292,12 @@ impl<'a> ED047TC1<'a> {
     }
 
     pub(crate) fn set_buffer(&mut self, data: &[u8]) -> crate::Result<()> {
-        let mut dma_buf = self.dma_buf.take().ok_or(crate::Error::Unknown)?;
+        let dma_buf = self.dma_buf.as_mut().ok_or(crate::Error::BusLost)?;
+        if data.len() > DMA_BUFFER_SIZE {
+            return Err(crate::Error::BufferSize);
+        }
         dma_buf.as_mut_slice().fill(0);
         dma_buf.as_mut_slice()[..data.len()].copy_from_slice(data);
-        self.dma_buf = Some(dma_buf);
         Ok(())
     }
 }
@@ -0,0 +1,143 @@
+//! Geometry and pixel layout of the framebuffer.
+//!
+//! The clipping and the pixel packing don't depend on the peripherals, so
+//! [Display](crate::Display) and the `embedded-graphics` integration share
+//! them with the host tests.
+
+use crate::{depth::PixelDepth, Error, Result};
+
+/// Width of the screen.
+pub(crate) const WIDTH: u16 = 960;
+/// Height of the screen.
+pub(crate) const HEIGHT: u16 = 540;
+/// Bounding box of the screen.
+pub(crate) const BOUNDING_BOX: Rectangle = Rectangle {
+    x: 0,
+    y: 0,
+    width: WIDTH,
+    height: HEIGHT,
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Rectangle {
+    pub x: u16,
+    pub y: u16,
+    pub width: u16,
+    pub height: u16,
+}
+
+impl Rectangle {
+    /// Returns `true` if the point is inside the rectangle.
+    pub fn contains(&self, x: u16, y: u16) -> bool {
+        x >= self.x && y >= self.y && x - self.x < self.width && y - self.y < self.height
+    }
+
+    /// Returns the area covered by both rectangles or `None` if they don't
+    /// overlap.
+    pub fn intersection(&self, other: &Rectangle) -> Option<Rectangle> {
+        let x = self.x.max(other.x);
+        let y = self.y.max(other.y);
+        let x_end = (self.x as u32 + self.width as u32).min(other.x as u32 + other.width as u32);
+        let y_end = (self.y as u32 + self.height as u32).min(other.y as u32 + other.height as u32);
+        if x as u32 >= x_end || y as u32 >= y_end {
+            return None;
+        }
+        Some(Rectangle {
+            x,
+            y,
+            width: (x_end - x as u32) as u16,
+            height: (y_end - y as u32) as u16,
+        })
+    }
+}
+
+/// Converts signed coordinates, e.g. of `embedded-graphics`, into a point on
+/// the screen or `None` if the point is outside.
+pub(crate) fn screen_point(x: i32, y: i32) -> Option<(u16, u16)> {
+    let (Ok(x), Ok(y)) = (u16::try_from(x), u16::try_from(y)) else {
+        return None;
+    };
+    BOUNDING_BOX.contains(x, y).then_some((x, y))
+}
+
+/// Converts an area with signed coordinates into the part covered by the
+/// screen, an empty rectangle if it doesn't overlap the screen.
+pub(crate) fn screen_area(x: i32, y: i32, width: u32, height: u32) -> Rectangle {
+    // clamp in 64 bit, so the end of the area can't overflow
+    let clamp = |start: i32, len: u32, max: u16| {
+        let end = (start as i64 + len as i64).clamp(0, max as i64);
+        let start = (start as i64).clamp(0, end);
+        (start as u16, (end - start) as u16)
+    };
+    let (x, width) = clamp(x, width, WIDTH);
+    let (y, height) = clamp(y, height, HEIGHT);
+    Rectangle {
+        x,
+        y,
+        width,
+        height,
+    }
+}
+
+/// Writes a pixel inside the `clip` rectangle and returns `true` if it has
+/// been written.
+///
+/// Returns [Error::OutOfBounds] if the pixel is outside the screen and
+/// [Error::InvalidColor] if the color exceeds [PixelDepth::MAX_COLOR].
+pub(crate) fn set_pixel<D: PixelDepth>(
+    buffer: &mut [u8],
+    clip: Option<Rectangle>,
+    x: u16,
+    y: u16,
+    color: u8,
+) -> Result<bool> {
+    if x >= WIDTH || y >= HEIGHT {
+        return Err(Error::OutOfBounds);
+    }
+    if color > D::MAX_COLOR {
+        return Err(Error::InvalidColor);
+    }
+    if !clip.is_some_and(|clip| clip.contains(x, y)) {
+        return Ok(false);
+    }
+    write_pixel::<D>(buffer, x as usize, y as usize, color);
+    Ok(true)
+}
+
+/// Fills the part of `area` inside the `clip` rectangle, which has to be
+/// inside the screen, and returns the filled area.
+///
+/// Returns [Error::InvalidColor] if the color exceeds
+/// [PixelDepth::MAX_COLOR].
+pub(crate) fn fill_rect<D: PixelDepth>(
+    buffer: &mut [u8],
+    clip: Option<Rectangle>,
+    area: Rectangle,
+    color: u8,
+) -> Result<Option<Rectangle>> {
+    if color > D::MAX_COLOR {
+        return Err(Error::InvalidColor);
+    }
+    let area = clip.and_then(|clip| clip.intersection(&area));
+    if let Some(area) = area {
+        fill_area::<D>(buffer, area, color);
+    }
+    Ok(area)
+}
+
+/// Fills an area inside the screen, the color has to be valid.
+pub(crate) fn fill_area<D: PixelDepth>(buffer: &mut [u8], area: Rectangle, color: u8) {
+    for y in area.y as usize..area.y as usize + area.height as usize {
+        for x in area.x as usize..area.x as usize + area.width as usize {
+            write_pixel::<D>(buffer, x, y, color);
+        }
+    }
+}
+
+/// Writes a pixel to a buffer with the layout of the framebuffer.
+pub(crate) fn write_pixel<D: PixelDepth>(buffer: &mut [u8], x: usize, y: usize, color: u8) {
+    let index = y * D::LINE_BYTES + x / D::PIXELS_PER_BYTE;
+    let shift = (x % D::PIXELS_PER_BYTE) * D::BITS;
+    buffer[index] = (buffer[index] & !(D::MAX_COLOR << shift)) | (color << shift);
+}
@@ -1,44 +1,75 @@
-use embedded_graphics_core::{pixelcolor::Gray4, prelude::*};
+use embedded_graphics_core::{
+    pixelcolor::{BinaryColor, Gray2, Gray4},
+    prelude::*,
+};
 
-use crate::{display::Display, Error};
+use crate::{
+    depth::{Bpp1, Bpp2, Bpp4, PixelDepth},
+    display::Display,
+    framebuffer,
+    Error,
+};
 
-impl<'a> DrawTarget for Display<'a> {
-    type Color = Gray4;
+/// Implements [DrawTarget] for a pixel depth, `$raw` converts the color into
+/// the raw pixel value.
+macro_rules! impl_draw_target {
+    ($depth:ty, $color:ty, $raw:expr) => {
+        impl<'a> DrawTarget for Display<'a, $depth> {
+            type Color = $color;
 
-    type Error = Error;
+            type Error = Error;
 
-    fn draw_iter<I>(&mut self, pixels: I) -> Result<(), Self::Error>
-    where
-        I: IntoIterator<Item = Pixel<Self::Color>>,
-    {
-        for Pixel(coord, color) in pixels.into_iter() {
-            let result = self.set_pixel(coord.x as u16, coord.y as u16, color.luma());
-            if matches!(result, Err(Error::OutOfBounds)) {
-                continue;
+            fn draw_iter<I>(&mut self, pixels: I) -> Result<(), Self::Error>
+            where
+                I: IntoIterator<Item = Pixel<Self::Color>>,
+            {
+                for Pixel(coord, color) in pixels.into_iter() {
+                    let Some((x, y)) = framebuffer::screen_point(coord.x, coord.y) else {
+                        continue;
+                    };
+                    self.set_pixel(x, y, $raw(color))?;
+                }
+                Ok(())
             }
-            result?;
-        }
-        Ok(())
-    }
 
-    fn clear(&mut self, color: Self::Color) -> Result<(), Self::Error> {
-        self.fill(color.luma())
-    }
+            fn fill_solid(
+                &mut self,
+                area: &embedded_graphics_core::primitives::Rectangle,
+                color: Self::Color,
+            ) -> Result<(), Self::Error> {
+                self.fill_rect((*area).into(), $raw(color))
+            }
+
+            fn clear(&mut self, color: Self::Color) -> Result<(), Self::Error> {
+                self.fill($raw(color))
+            }
+        }
+    };
 }
 
-impl<'a> OriginDimensions for Display<'a> {
+// `On` pixels are black, like ink on paper
+impl_draw_target!(Bpp1, BinaryColor, |color: BinaryColor| color.is_off() as u8);
+impl_draw_target!(Bpp2, Gray2, |color: Gray2| color.luma());
+impl_draw_target!(Bpp4, Gray4, |color: Gray4| color.luma());
+
+impl<D: PixelDepth> OriginDimensions for Display<'_, D> {
     fn size(&self) -> Size {
-        Size::new(Self::WIDTH as u32, Self::HEIGHT as u32)
+        Size::new(Display::WIDTH as u32, Display::HEIGHT as u32)
     }
 }
 
 impl From<embedded_graphics_core::primitives::Rectangle> for crate::display::Rectangle {
     fn from(val: embedded_graphics_core::primitives::Rectangle) -> Self {
-        crate::display::Rectangle {
-            x: val.top_left.x as u16,
-            y: val.top_left.y as u16,
-            width: val.size.width as u16,
-            height: val.size.height as u16,
-        }
+        let Point { x, y } = val.top_left;
+        framebuffer::screen_area(x, y, val.size.width, val.size.height)
+    }
+}
+
+impl From<crate::display::Rectangle> for embedded_graphics_core::primitives::Rectangle {
+    fn from(val: crate::display::Rectangle) -> Self {
+        embedded_graphics_core::primitives::Rectangle::new(
+            Point::new(val.x as i32, val.y as i32),
+            Size::new(val.width as u32, val.height as u32),
+        )
     }
 }
@@ -0,0 +1,224 @@
+//! Native 4bpp image format.
+//!
+//! Images are stored in the framebuffer layout of [Display](crate::Display),
+//! so uncompressed images can be copied into the framebuffer row by row. The
+//! format is small enough to be embedded using `include_bytes!` and can be
+//! created on the host using [Image4bpp::encode].
+//!
+//! | Offset | Size | Content                                             |
+//! |--------|------|-----------------------------------------------------|
+//! | 0      | 4    | Magic `EPI4`                                        |
+//! | 4      | 1    | Version                                             |
+//! | 5      | 1    | Flags, bit 0: compressed, bit 1: transparent        |
+//! | 6      | 1    | Transparent color                                   |
+//! | 7      | 1    | Reserved                                            |
+//! | 8      | 2    | Width in pixels (little endian)                     |
+//! | 10     | 2    | Height in pixels (little endian)                    |
+//! | 12     |      | Pixel data, `(width + 1) / 2` bytes per row         |
+//!
+//! Compressed pixel data is encoded using [codec::encode].
+
+use crate::{
+    blit::{self, RasterOp},
+    codec,
+    depth::{Bpp4, PixelDepth},
+    framebuffer::Rectangle,
+    Error,
+    Result,
+};
+
+const MAGIC: [u8; 4] = *b"EPI4";
+const VERSION: u8 = 1;
+const FLAG_COMPRESSED: u8 = 1 << 0;
+const FLAG_TRANSPARENT: u8 = 1 << 1;
+
+/// 4bpp image in the native format of the driver, drawn using
+/// [Display::draw_image](crate::Display::draw_image).
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Image4bpp<'a> {
+    width: u16,
+    height: u16,
+    compressed: bool,
+    transparent: Option<u8>,
+    data: &'a [u8],
+}
+
+impl<'a> Image4bpp<'a> {
+    /// Size of the header in bytes.
+    pub const HEADER_SIZE: usize = 12;
+
+    /// Parses an image created by [Image4bpp::encode].
+    ///
+    /// Returns [Error::InvalidEncoding] if the header is invalid or the size
+    /// of uncompressed pixel data doesn't match the dimensions. Compressed
+    /// pixel data is validated while drawing.
+    pub fn new(data: &'a [u8]) -> Result<Self> {
+        let header = data
+            .get(..Self::HEADER_SIZE)
+            .ok_or(Error::InvalidEncoding)?;
+        let image = Image4bpp {
+            data: &data[Self::HEADER_SIZE..],
+            ..Image4bpp::parse_header(header)?
+        };
+        if !image.compressed && image.data.len() != image.stride() * image.height as usize {
+            return Err(Error::InvalidEncoding);
+        }
+        Ok(image)
+    }
+
+    /// Parses the first [Image4bpp::HEADER_SIZE] bytes of an image, the
+    /// returned image has no pixel data.
+    pub(crate) fn parse_header(header: &[u8]) -> Result<Image4bpp<'static>> {
+        if header.len() < Self::HEADER_SIZE || header[0..4] != MAGIC || header[4] != VERSION {
+            return Err(Error::InvalidEncoding);
+        }
+        let flags = header[5];
+        let transparent = (flags & FLAG_TRANSPARENT != 0).then_some(header[6]);
+        if transparent.is_some_and(|color| color > 0x0F) {
+            return Err(Error::InvalidEncoding);
+        }
+        Ok(Image4bpp {
+            width: u16::from_le_bytes([header[8], header[9]]),
+            height: u16::from_le_bytes([header[10], header[11]]),
+            compressed: flags & FLAG_COMPRESSED != 0,
+            transparent,
+            data: &[],
+        })
+    }
+
+    /// Encodes packed 4bpp pixel data into `out` and returns the number of
+    /// bytes written. `data` uses the layout of the framebuffer with rows of
+    /// `(width + 1) / 2` bytes. Pixels of the `transparent` color are not
+    /// drawn.
+    ///
+    /// Returns [Error::SizeMismatch] if `data` doesn't match the dimensions,
+    /// [Error::InvalidColor] if the transparent color exceeds 0x0F and
+    /// [Error::BufferTooSmall] if `out` can't hold the image.
+    pub fn encode(
+        width: u16,
+        height: u16,
+        data: &[u8],
+        transparent: Option<u8>,
+        compress: bool,
+        out: &mut [u8],
+    ) -> Result<usize> {
+        if data.len() != (width as usize).div_ceil(2) * height as usize {
+            return Err(Error::SizeMismatch);
+        }
+        if transparent.is_some_and(|color| color > 0x0F) {
+            return Err(Error::InvalidColor);
+        }
+        if out.len() < Self::HEADER_SIZE {
+            return Err(Error::BufferTooSmall);
+        }
+        let (header, pixels) = out.split_at_mut(Self::HEADER_SIZE);
+        header.fill(0);
+        header[0..4].copy_from_slice(&MAGIC);
+        header[4] = VERSION;
+        if compress {
+            header[5] |= FLAG_COMPRESSED;
+        }
+        if let Some(color) = transparent {
+            header[5] |= FLAG_TRANSPARENT;
+            header[6] = color;
+        }
+        header[8..10].copy_from_slice(&width.to_le_bytes());
+        header[10..12].copy_from_slice(&height.to_le_bytes());
+        let len = match compress {
+            true => codec::encode(data, pixels)?,
+            false => {
+                pixels
+                    .get_mut(..data.len())
+                    .ok_or(Error::BufferTooSmall)?
+                    .copy_from_slice(data);
+                data.len()
+            }
+        };
+        Ok(Self::HEADER_SIZE + len)
+    }
+
+    /// Width in pixels.
+    pub fn width(&self) -> u16 {
+        self.width
+    }
+
+    /// Height in pixels.
+    pub fn height(&self) -> u16 {
+        self.height
+    }
+
+    /// Number of bytes per row of the uncompressed pixel data.
+    pub fn stride(&self) -> usize {
+        (self.width as usize).div_ceil(2)
+    }
+
+    /// Returns `true` if the pixel data is [compressed](crate::codec).
+    pub fn is_compressed(&self) -> bool {
+        self.compressed
+    }
+
+    /// Color of the pixels which are not drawn.
+    pub fn transparent(&self) -> Option<u8> {
+        self.transparent
+    }
+
+    /// Pixel data without the header.
+    pub fn data(&self) -> &'a [u8] {
+        self.data
+    }
+
+    /// Draws the part of the image with its top left corner at `x`, `y`
+    /// inside `target` into a [Bpp4] framebuffer. `target` has to be inside
+    /// the screen and the area of the image. Compressed images are decoded
+    /// row by row, skipping the invisible parts.
+    pub(crate) fn draw(&self, buffer: &mut [u8], x: u16, y: u16, target: Rectangle) -> Result<()> {
+        let area = Rectangle {
+            x,
+            y,
+            width: self.width,
+            height: self.height,
+        };
+        let op = match self.transparent {
+            None => RasterOp::Copy,
+            Some(key) => RasterOp::Transparent(key),
+        };
+        let stride = self.stride();
+        if !self.compressed {
+            blit::blit(buffer, Some(target), area, self.data, stride, op, None)?;
+            return Ok(());
+        }
+
+        // bytes of each image row covering the visible columns
+        let first = (target.x - x) as usize / 2;
+        let last = (target.x - x + target.width) as usize;
+        let bytes = last.div_ceil(2) - first;
+        let columns = Rectangle {
+            x: x + first as u16 * 2,
+            width: (last - first * 2) as u16,
+            ..target
+        };
+        let mut decoder = codec::Decoder::new(self.data);
+        let mut row = [0u8; Bpp4::LINE_BYTES + 1];
+        decoder.skip((target.y - y) as usize * stride)?;
+        for row_y in target.y..target.y + target.height {
+            decoder.skip(first)?;
+            decoder.read(&mut row[..bytes])?;
+            decoder.skip(stride - first - bytes)?;
+            let row_area = Rectangle {
+                y: row_y,
+                height: 1,
+                ..columns
+            };
+            blit::blit(
+                buffer,
+                Some(target),
+                row_area,
+                &row[..bytes],
+                bytes,
+                op,
+                None,
+            )?;
+        }
+        Ok(())
+    }
+}
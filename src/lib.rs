@@ -1,8 +1,9 @@
 //! Simple driver for the LilyGo T5 4.7 inch E-Ink Display.
 //! The driver only supports the V2.3 hardware variant (ESP32-S3)
 //!
-//! This library depends on alloc and requires you to set up an global allocator
-//! for the PSRAM.
+//! By default this library depends on alloc and requires you to set up an
+//! global allocator for the PSRAM. Without the `alloc` feature the framebuffer
+//! has to be provided using [Display::new_with_framebuffer].
 //!
 //!
 //! Built using [`esp-hal`] and [`embedded-graphics`]
@@ -65,19 +66,43 @@
 //! }
 #![no_std]
 
+#[cfg(feature = "alloc")]
 extern crate alloc;
 
+// This must go first, so the logging macros are visible to all other modules.
+#[macro_use]
+mod fmt;
+
+pub mod blit;
+pub mod calibration;
+pub mod codec;
+pub mod depth;
 pub mod display;
+pub mod dither;
 
 #[cfg(feature = "embedded-graphics")]
 pub mod graphics;
 
+pub mod image;
+
+pub mod repair;
+pub mod tone;
+pub mod tps65185;
+
 mod battery;
+mod dirty_rows;
 mod ed047tc1;
+mod framebuffer;
+mod panel_config;
 mod rmt;
+mod screenshot;
+mod stream;
+mod timings;
+mod waveform;
 
 /// Errors
 #[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub enum Error {
     /// Pass-through
     Rmt(esp_hal::rmt::Error),
@@ -85,22 +110,98 @@ pub enum Error {
     Dma(esp_hal::dma::DmaError),
     /// Pass-through
     DmaBuffer(esp_hal::dma::DmaBufError),
+    /// Pass-through
+    I8080Config(esp_hal::lcd_cam::lcd::i8080::ConfigError),
+    /// Reading the ADC failed.
+    Adc,
+    /// The i8080 bus, the DMA buffer or the RMT channel is not available.
+    BusLost,
+    /// Provided data exceeds the size of the DMA buffer.
+    BufferSize,
     /// Provided pixel coordinates exceed the display boundary.
     OutOfBounds,
     /// Provided color exceeds the allowed range of 0x0 - 0x0F
     InvalidColor,
-    Unknown,
+    /// Pass-through
+    I2c(embedded_hal::i2c::ErrorKind),
+    /// The PMIC did not report all rails as good in time.
+    PowerGood,
+    /// Provided VCOM voltage exceeds the range supported by the PMIC.
+    InvalidVcom,
+    /// The device did not respond in time.
+    Timeout,
+    /// The provided configuration blob is corrupted or incompatible.
+    InvalidConfig,
+    /// Screen tracking has not been enabled.
+    ScreenNotTracked,
+    /// The provided output buffer is too small.
+    BufferTooSmall,
+    /// The provided encoded image data or snapshot is corrupted.
+    InvalidEncoding,
+    /// The provided buffers differ in size.
+    SizeMismatch,
+    /// The provided timings are out of range.
+    InvalidTimings,
+    /// The provided tone curve is not monotonic.
+    InvalidToneCurve,
+    /// Pass-through
+    Io(embedded_io::ErrorKind),
+    /// The format of the provided image is not supported.
+    UnsupportedFormat,
 }
 
+impl core::fmt::Display for Error {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Error::Rmt(err) => write!(f, "RMT error: {}", err),
+            Error::Dma(err) => write!(f, "DMA error: {:?}", err),
+            Error::DmaBuffer(err) => write!(f, "DMA buffer error: {:?}", err),
+            Error::I8080Config(err) => write!(f, "invalid i8080 configuration: {:?}", err),
+            Error::Adc => write!(f, "reading the ADC failed"),
+            Error::BusLost => write!(f, "i8080 bus, DMA buffer or RMT channel not available"),
+            Error::BufferSize => write!(f, "data exceeds the DMA buffer size"),
+            Error::OutOfBounds => write!(f, "pixel coordinates exceed the display boundary"),
+            Error::InvalidColor => write!(f, "color exceeds the range of 0x0 - 0xF"),
+            Error::I2c(err) => write!(f, "I2C error: {}", err),
+            Error::PowerGood => write!(f, "PMIC rails did not report power good in time"),
+            Error::InvalidVcom => write!(f, "VCOM voltage exceeds the supported range"),
+            Error::Timeout => write!(f, "device did not respond in time"),
+            Error::InvalidConfig => write!(f, "configuration blob is corrupted or incompatible"),
+            Error::ScreenNotTracked => write!(f, "screen tracking is not enabled"),
+            Error::BufferTooSmall => write!(f, "output buffer is too small"),
+            Error::InvalidEncoding => write!(f, "encoded image data is corrupted"),
+            Error::SizeMismatch => write!(f, "buffer sizes do not match"),
+            Error::InvalidTimings => write!(f, "timings are out of range"),
+            Error::InvalidToneCurve => write!(f, "tone curve is not monotonic"),
+            Error::Io(err) => write!(f, "I/O error: {}", err),
+            Error::UnsupportedFormat => write!(f, "image format is not supported"),
+        }
+    }
+}
+
+impl core::error::Error for Error {}
+
 type Result<T> = core::result::Result<T, Error>;
 
+#[cfg(feature = "embedded-graphics")]
+pub use crate::dither::DitheringDrawTarget;
 pub use crate::{
     battery::Battery,
-    display::{Display, DrawMode},
-    ed047tc1::PinConfig,
+    blit::{Mask, RasterOp},
+    calibration::PanelConfig,
+    depth::{Bpp1, Bpp2, Bpp4},
+    dirty_rows::DirtyRows,
+    display::{ClearStrategy, Display, DrawMode, Timings},
+    dither::Dithering,
+    ed047tc1::{PinConfig, PinConfigV23},
+    image::Image4bpp,
+    repair::{Repair, RepairConfig},
+    tone::ToneCurve,
+    tps65185::Tps65185,
 };
 
-/// Convenience macro to build the pin config struct.
+/// Convenience macro to build the pin config struct for the V2.3 board
+/// ([PinConfigV23]).
 #[macro_export]
 macro_rules! pin_config {
     ($($name:ident),*) => {
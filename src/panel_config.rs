@@ -0,0 +1,111 @@
+//! Persistable panel configuration.
+//!
+//! [PanelConfig] is plain data, so the serialization is shared with the host
+//! tests. The calibration workflows filling it are part of
+//! [calibration](crate::calibration).
+
+use crate::{timings::Timings, tone::ToneCurve, Error, Result};
+
+/// Persistable panel configuration.
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct PanelConfig {
+    /// VCOM voltage in millivolts (the actual voltage is negative).
+    pub vcom_mv: u16,
+    /// Timings used to drive the panel.
+    pub timings: Timings,
+    /// Lightness of the gray levels.
+    pub tone_curve: ToneCurve,
+}
+
+impl PanelConfig {
+    const MAGIC: [u8; 4] = *b"EP47";
+    const VERSION: u8 = 2;
+    /// Size of blobs written by version 1, which only contain the VCOM
+    /// voltage.
+    const SIZE_V1: usize = 10;
+
+    /// Size of the serialized configuration in bytes.
+    pub const SIZE: usize = 92;
+
+    /// Creates a configuration with the default timings and tone curve.
+    pub fn new(vcom_mv: u16) -> Self {
+        PanelConfig {
+            vcom_mv,
+            timings: Timings::DEFAULT,
+            tone_curve: ToneCurve::DEFAULT,
+        }
+    }
+
+    /// Serialize the configuration into a small blob.
+    pub fn to_bytes(&self) -> [u8; Self::SIZE] {
+        let mut bytes = [0u8; Self::SIZE];
+        bytes[0..4].copy_from_slice(&Self::MAGIC);
+        bytes[4] = Self::VERSION;
+        bytes[6..8].copy_from_slice(&self.vcom_mv.to_le_bytes());
+        let timings = &self.timings;
+        let cycles = timings
+            .contrast_cycles
+            .iter()
+            .chain(&timings.contrast_cycles_white);
+        for (chunk, cycle) in bytes[8..68].chunks_exact_mut(2).zip(cycles) {
+            chunk.copy_from_slice(&cycle.to_le_bytes());
+        }
+        bytes[68] = timings.frame_count;
+        bytes[70..72].copy_from_slice(&timings.clear_cycles.to_le_bytes());
+        bytes[72..74].copy_from_slice(&timings.clear_cycle_time.to_le_bytes());
+        bytes[74..90].copy_from_slice(self.tone_curve.levels());
+        let checksum = fletcher16(&bytes[..Self::SIZE - 2]);
+        bytes[Self::SIZE - 2..].copy_from_slice(&checksum.to_le_bytes());
+        bytes
+    }
+
+    /// Deserialize a blob created by [PanelConfig::to_bytes]. Blobs written
+    /// by older versions, which only contain the VCOM voltage, use the
+    /// default timings and tone curve.
+    ///
+    /// Returns [Error::InvalidConfig] if the blob is corrupted or has been
+    /// written by an incompatible version.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        if bytes.len() < Self::SIZE_V1 || bytes[0..4] != Self::MAGIC {
+            return Err(Error::InvalidConfig);
+        }
+        let size = match bytes[4] {
+            1 => Self::SIZE_V1,
+            Self::VERSION if bytes.len() >= Self::SIZE => Self::SIZE,
+            _ => return Err(Error::InvalidConfig),
+        };
+        let checksum = u16::from_le_bytes([bytes[size - 2], bytes[size - 1]]);
+        if fletcher16(&bytes[..size - 2]) != checksum {
+            return Err(Error::InvalidConfig);
+        }
+        let mut config = PanelConfig::new(u16::from_le_bytes([bytes[6], bytes[7]]));
+        if size == Self::SIZE_V1 {
+            return Ok(config);
+        }
+
+        let word = |offset: usize| u16::from_le_bytes([bytes[offset], bytes[offset + 1]]);
+        let timings = &mut config.timings;
+        for i in 0..15 {
+            timings.contrast_cycles[i] = word(8 + i * 2);
+            timings.contrast_cycles_white[i] = word(38 + i * 2);
+        }
+        timings.frame_count = bytes[68];
+        timings.clear_cycles = word(70);
+        timings.clear_cycle_time = word(72);
+        timings.validate().map_err(|_| Error::InvalidConfig)?;
+        let mut levels = [0u8; 16];
+        levels.copy_from_slice(&bytes[74..90]);
+        config.tone_curve = ToneCurve::new(levels).map_err(|_| Error::InvalidConfig)?;
+        Ok(config)
+    }
+}
+
+fn fletcher16(data: &[u8]) -> u16 {
+    let (mut a, mut b) = (0u16, 0u16);
+    for &byte in data {
+        a = (a + byte as u16) % 255;
+        b = (b + a) % 255;
+    }
+    (b << 8) | a
+}
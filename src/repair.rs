@@ -0,0 +1,159 @@
+//! Screen repair routine.
+//!
+//! Implements the repair methodology
+//! [provided by lilygo](https://github.com/Xinyuan-LilyGO/LilyGo-EPD47/blob/master/examples/screen_repair/screen_repair.ino)
+//! as a step-wise state machine. Every call of [Repair::step] performs a
+//! short amount of work and returns how long to wait before the next step, so
+//! the routine can be driven from a loop or an async task and be cancelled
+//! at any time.
+
+use crate::Result;
+
+/// Configuration of the repair routine.
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct RepairConfig {
+    /// Number of black pulses.
+    pub black_iterations: u16,
+    /// Number of white pulses.
+    pub white_iterations: u16,
+    /// Output time of a row for each pulse.
+    pub pulse_time: u16,
+    /// Time to wait between two pulses in milliseconds.
+    pub wait_ms: u32,
+}
+
+impl RepairConfig {
+    /// Configuration of the original routine, which takes about 30 seconds.
+    pub const DEFAULT: RepairConfig = RepairConfig {
+        black_iterations: 20,
+        white_iterations: 40,
+        pulse_time: 50,
+        wait_ms: 500,
+    };
+}
+
+impl Default for RepairConfig {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
+
+/// Result of a single [Repair::step].
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum RepairStatus {
+    /// The routine is in progress, wait the given number of milliseconds
+    /// before calling [Repair::step] again.
+    Wait(u32),
+    /// The routine has finished.
+    Done,
+    /// The routine has been cancelled, the screen has been cleared.
+    Cancelled,
+}
+
+/// Work of a single [Repair::step] on the display.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum Action {
+    /// Clear the screen.
+    Clear,
+    /// Push the whole screen towards black with the given output time.
+    Black(u16),
+    /// Push the whole screen towards white with the given output time.
+    White(u16),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Phase {
+    ClearBeforeBlack,
+    Black(u16),
+    White(u16),
+    Done,
+}
+
+/// Step-wise screen repair routine.
+pub struct Repair {
+    config: RepairConfig,
+    phase: Phase,
+    completed: u32,
+    cancelled: bool,
+}
+
+impl Repair {
+    /// Create a new routine, nothing is drawn until [Repair::step] is called.
+    pub fn new(config: RepairConfig) -> Self {
+        Repair {
+            config,
+            phase: Phase::ClearBeforeBlack,
+            completed: 0,
+            cancelled: false,
+        }
+    }
+
+    /// Advances the routine by a single step, `run` performs the [Action]
+    /// of the step on the display. The routine stays at the step if `run`
+    /// fails, except when it has been cancelled.
+    pub(crate) fn advance(
+        &mut self,
+        run: impl FnOnce(Action) -> Result<()>,
+    ) -> Result<RepairStatus> {
+        if self.phase == Phase::Done {
+            return Ok(match self.cancelled {
+                true => RepairStatus::Cancelled,
+                false => RepairStatus::Done,
+            });
+        }
+        if self.cancelled {
+            debug!("repair: cancelled");
+            self.phase = Phase::Done;
+            run(Action::Clear)?;
+            return Ok(RepairStatus::Cancelled);
+        }
+
+        let config = self.config;
+        let (action, next, wait) = match self.phase {
+            Phase::ClearBeforeBlack => (Action::Clear, Phase::Black(0), 0),
+            Phase::Black(i) if i < config.black_iterations => (
+                Action::Black(config.pulse_time),
+                Phase::Black(i + 1),
+                config.wait_ms,
+            ),
+            Phase::Black(_) => (Action::Clear, Phase::White(0), 0),
+            Phase::White(i) if i < config.white_iterations => (
+                Action::White(config.pulse_time),
+                Phase::White(i + 1),
+                config.wait_ms,
+            ),
+            Phase::White(_) | Phase::Done => (Action::Clear, Phase::Done, 0),
+        };
+        run(action)?;
+        self.phase = next;
+        self.completed += 1;
+
+        Ok(match next {
+            Phase::Done => RepairStatus::Done,
+            _ => RepairStatus::Wait(wait),
+        })
+    }
+
+    /// Cancels the routine. The next call of [Repair::step] clears the screen
+    /// and returns [RepairStatus::Cancelled]. Has no effect if the routine
+    /// has already finished.
+    pub fn cancel(&mut self) {
+        if self.phase != Phase::Done {
+            self.cancelled = true;
+        }
+    }
+
+    /// Returns the number of completed and total steps. Every clear and
+    /// every pulse is a step.
+    pub fn progress(&self) -> (u32, u32) {
+        let total = self.config.black_iterations as u32 + self.config.white_iterations as u32 + 3;
+        (self.completed.min(total), total)
+    }
+
+    /// Returns `true` if the routine has finished or has been cancelled.
+    pub fn is_finished(&self) -> bool {
+        self.phase == Phase::Done
+    }
+}
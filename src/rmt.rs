@@ -1,5 +1,5 @@
 use esp_hal::{
-    gpio::Level,
+    gpio::{AnyPin, Level, Pin},
     peripherals,
     rmt,
     rmt::{Channel, PulseCode, Tx, TxChannelCreator},
@@ -10,13 +10,15 @@ use esp_hal::{
 pub(crate) struct Rmt<'a> {
     tx_channel: Option<Channel<'a, Blocking, Tx>>,
     _rmt: peripherals::RMT<'a>,
+    pin: AnyPin<'a>,
 }
 
 impl<'a> Rmt<'a> {
-    pub(crate) fn new(_rmt: peripherals::RMT<'a>) -> Self {
+    pub(crate) fn new(_rmt: peripherals::RMT<'a>, pin: AnyPin<'a>) -> Self {
         Rmt {
             tx_channel: None,
             _rmt,
+            pin,
         }
     }
 
@@ -39,7 +41,7 @@ impl<'a> Rmt<'a> {
         let tx_channel = rmt
             .channel1
             .configure_tx(
-                unsafe { peripherals::GPIO38::steal() }, // TODO: find better solution
+                unsafe { AnyPin::steal(self.pin.number()) }, // TODO: find better solution
                 config,
             )
             .map_err(crate::Error::Rmt)?;
@@ -49,7 +51,7 @@ impl<'a> Rmt<'a> {
 
     pub(crate) fn pulse(&mut self, high: u16, low: u16, wait: bool) -> Result<(), crate::Error> {
         self.ensure_channel()?;
-        let tx_channel = self.tx_channel.take().ok_or(crate::Error::Unknown)?;
+        let tx_channel = self.tx_channel.take().ok_or(crate::Error::BusLost)?;
         let data = if high > 0 {
             [
                 PulseCode::new(Level::High, high, Level::Low, low),
@@ -65,11 +67,13 @@ impl<'a> Rmt<'a> {
         // FIXME: This is the culprit.. We need the channel later again but can't wait
         // due to some time sensitive operations. Not sure how to solve this
         if wait {
-            self.tx_channel = Some(
-                tx.wait()
-                    .map_err(|(err, _)| err)
-                    .map_err(crate::Error::Rmt)?,
-            );
+            match tx.wait() {
+                Ok(tx_channel) => self.tx_channel = Some(tx_channel),
+                Err((err, tx_channel)) => {
+                    self.tx_channel = Some(tx_channel);
+                    return Err(crate::Error::Rmt(err));
+                }
+            }
         }
         Ok(())
     }
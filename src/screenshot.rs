@@ -0,0 +1,186 @@
+//! Export of the framebuffer as PGM or PNG image.
+//!
+//! Both formats are written uncompressed in small pieces, so a screenshot can
+//! be streamed over UART or USB-serial without additional memory.
+//! [Display::write_pgm](crate::Display::write_pgm) and
+//! [Display::write_png](crate::Display::write_png) export the framebuffer of
+//! the display.
+
+use embedded_io::Write;
+
+use crate::{
+    depth::PixelDepth,
+    framebuffer::{HEIGHT, WIDTH},
+};
+
+/// Header of a binary PGM image with the size of the screen.
+const PGM_HEADER: &[u8] = b"P5\n960 540\n255\n";
+const _: () = assert!(WIDTH == 960 && HEIGHT == 540);
+
+const PNG_SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1A, b'\n'];
+
+/// Maximum length of a stored deflate block.
+const MAX_BLOCK: usize = 0xFFFF;
+
+const CRC_TABLE: [u32; 256] = crc_table();
+
+/// Writes a framebuffer with pixel depth `D` as binary 8-bit PGM image
+/// (`P5`).
+pub(crate) fn write_pgm<D: PixelDepth, W: Write>(
+    framebuffer: &[u8],
+    writer: &mut W,
+) -> Result<(), W::Error> {
+    writer.write_all(PGM_HEADER)?;
+
+    let scale = 0xFF / D::MAX_COLOR;
+    let mut pixels = [0u8; 64];
+    for chunk in framebuffer.chunks(pixels.len() / D::PIXELS_PER_BYTE) {
+        let mut len = 0;
+        for byte in chunk {
+            for p in 0..D::PIXELS_PER_BYTE {
+                pixels[len] = ((byte >> (p * D::BITS)) & D::MAX_COLOR) * scale;
+                len += 1;
+            }
+        }
+        writer.write_all(&pixels[..len])?;
+    }
+    writer.flush()
+}
+
+/// Writes a framebuffer with pixel depth `D` as grayscale PNG image with a
+/// bit depth of `D::BITS`.
+pub(crate) fn write_png<D: PixelDepth, W: Write>(
+    framebuffer: &[u8],
+    writer: &mut W,
+) -> Result<(), W::Error> {
+    writer.write_all(&PNG_SIGNATURE)?;
+
+    let mut header = [0u8; 13];
+    header[0..4].copy_from_slice(&(WIDTH as u32).to_be_bytes());
+    header[4..8].copy_from_slice(&(HEIGHT as u32).to_be_bytes());
+    // bit depth, grayscale, deflate, no filter, no interlace
+    header[8] = D::BITS as u8;
+    let mut chunk = Chunk::start(writer, *b"IHDR", header.len())?;
+    chunk.write(&header)?;
+    chunk.finish()?;
+
+    // every row starts with its filter type
+    let raw_len = HEIGHT as usize * (1 + D::LINE_BYTES);
+    let blocks = raw_len.div_ceil(MAX_BLOCK);
+    // zlib header, block headers, data and checksum
+    let len = 2 + blocks * 5 + raw_len + 4;
+    let mut chunk = Chunk::start(writer, *b"IDAT", len)?;
+    // deflate with the smallest window, no dictionary
+    chunk.write(&[0x08, 0x1D])?;
+    let mut deflate = Deflate {
+        chunk,
+        remaining: raw_len,
+        block: 0,
+        adler: (1, 0),
+    };
+    let mut bytes = [0u8; 32];
+    for row in framebuffer.chunks(D::LINE_BYTES) {
+        deflate.write(&[0])?;
+        for chunk in row.chunks(bytes.len()) {
+            // PNG stores the left pixel in the most significant bits
+            for (target, &byte) in bytes.iter_mut().zip(chunk) {
+                *target = (0..D::PIXELS_PER_BYTE).fold(0, |result, p| {
+                    let pixel = (byte >> (p * D::BITS)) & D::MAX_COLOR;
+                    result | pixel << ((D::PIXELS_PER_BYTE - 1 - p) * D::BITS)
+                });
+            }
+            deflate.write(&bytes[..chunk.len()])?;
+        }
+    }
+    let (a, b) = deflate.adler;
+    let mut chunk = deflate.chunk;
+    chunk.write(&((b << 16) | a).to_be_bytes())?;
+    chunk.finish()?;
+
+    Chunk::start(writer, *b"IEND", 0)?.finish()?;
+    writer.flush()
+}
+
+/// PNG chunk of a known length, the checksum is calculated while writing.
+struct Chunk<'w, W: Write> {
+    writer: &'w mut W,
+    crc: u32,
+}
+
+impl<'w, W: Write> Chunk<'w, W> {
+    fn start(writer: &'w mut W, kind: [u8; 4], len: usize) -> Result<Self, W::Error> {
+        writer.write_all(&(len as u32).to_be_bytes())?;
+        let mut chunk = Chunk {
+            writer,
+            crc: 0xFFFF_FFFF,
+        };
+        chunk.write(&kind)?;
+        Ok(chunk)
+    }
+
+    fn write(&mut self, data: &[u8]) -> Result<(), W::Error> {
+        for &byte in data {
+            self.crc = CRC_TABLE[((self.crc ^ byte as u32) & 0xFF) as usize] ^ (self.crc >> 8);
+        }
+        self.writer.write_all(data)
+    }
+
+    fn finish(self) -> Result<(), W::Error> {
+        self.writer.write_all(&(!self.crc).to_be_bytes())
+    }
+}
+
+/// Uncompressed deflate stream split into stored blocks.
+struct Deflate<'w, W: Write> {
+    chunk: Chunk<'w, W>,
+    /// Bytes left in the stream.
+    remaining: usize,
+    /// Bytes left in the current block.
+    block: usize,
+    adler: (u32, u32),
+}
+
+impl<W: Write> Deflate<'_, W> {
+    fn write(&mut self, mut data: &[u8]) -> Result<(), W::Error> {
+        while !data.is_empty() {
+            if self.block == 0 {
+                let len = self.remaining.min(MAX_BLOCK) as u16;
+                let last = self.remaining <= MAX_BLOCK;
+                let [len_low, len_high] = len.to_le_bytes();
+                self.chunk
+                    .write(&[last as u8, len_low, len_high, !len_low, !len_high])?;
+                self.block = len as usize;
+            }
+            let len = self.block.min(data.len());
+            let (a, b) = &mut self.adler;
+            for &byte in &data[..len] {
+                *a = (*a + byte as u32) % 65521;
+                *b = (*b + *a) % 65521;
+            }
+            self.chunk.write(&data[..len])?;
+            self.block -= len;
+            self.remaining -= len;
+            data = &data[len..];
+        }
+        Ok(())
+    }
+}
+
+const fn crc_table() -> [u32; 256] {
+    let mut table = [0u32; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = i as u32;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = match crc & 1 {
+                1 => 0xEDB8_8320 ^ (crc >> 1),
+                _ => crc >> 1,
+            };
+            bit += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+    table
+}
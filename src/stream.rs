@@ -0,0 +1,465 @@
+//! Streaming decode of images into the framebuffer.
+//!
+//! [Display::draw_stream](crate::Display::draw_stream) reads an image from an
+//! [embedded_io::Read] and writes it row by row into the framebuffer, so
+//! images as large as the screen can be drawn without buffering them. The
+//! format is detected from the first bytes of the stream:
+//!
+//! - Binary PGM (`P5`) with up to 8 bits per sample
+//! - Uncompressed BMP with 1, 4, 8, 24 or 32 bits per pixel
+//! - The native [Image4bpp] format, raw or compressed
+//!
+//! PGM and BMP images are dithered to the gray levels of the display using
+//! its [ToneCurve](crate::ToneCurve). Native images contain 4bpp colors and
+//! are drawn as they are, displays with fewer gray levels keep the upper bits
+//! of each color.
+
+use embedded_io::{Error as _, Read};
+
+use crate::{
+    codec,
+    depth::PixelDepth,
+    dither::{Ditherer, Dithering},
+    framebuffer::{self, Rectangle},
+    image::Image4bpp,
+    tone::ToneCurve,
+    Error,
+    Result,
+};
+
+const WIDTH: usize = framebuffer::WIDTH as usize;
+
+/// Size of the buffer used to read from the stream.
+const BUFFER_SIZE: usize = 64;
+
+/// Size of the BMP file header.
+const BMP_FILE_HEADER_SIZE: usize = 14;
+/// Size of the `BITMAPINFOHEADER`, later versions extend it.
+const BMP_INFO_HEADER_SIZE: usize = 40;
+
+/// Image stream whose header has been read.
+pub(crate) struct Stream<'r, R: Read> {
+    input: Input<'r, R>,
+    header: Header,
+}
+
+impl<'r, R: Read> Stream<'r, R> {
+    /// Reads the header of the image from `reader`.
+    ///
+    /// Returns [Error::UnsupportedFormat] if the image format is not
+    /// supported, [Error::InvalidEncoding] if the header is corrupted or the
+    /// stream ends early and [Error::Io] if reading fails.
+    pub(crate) fn new(reader: &'r mut R) -> Result<Self> {
+        let mut input = Input {
+            reader,
+            buffer: [0; BUFFER_SIZE],
+            pos: 0,
+            len: 0,
+        };
+        let header = Header::parse(&mut input)?;
+        Ok(Stream { input, header })
+    }
+
+    /// Returns the area covered by the image with its top left corner at `x`,
+    /// `y`.
+    pub(crate) fn area(&self, x: u16, y: u16) -> Rectangle {
+        Rectangle {
+            x,
+            y,
+            width: self.header.width,
+            height: self.header.height,
+        }
+    }
+
+    /// Reads the image with its top left corner at `x`, `y` and writes the
+    /// part inside `target` into `buffer`, a framebuffer with pixel depth
+    /// `D`. `target`
+    /// has to be inside the screen and the [area](Stream::area) of the image,
+    /// reading stops after its last row.
+    pub(crate) fn draw<D: PixelDepth>(
+        &mut self,
+        buffer: &mut [u8],
+        x: u16,
+        y: u16,
+        target: Rectangle,
+        dithering: Dithering,
+        curve: &ToneCurve,
+    ) -> Result<()> {
+        let Stream { input, header } = self;
+        let mut ditherer = Ditherer::new(dithering);
+        let mut row = [0u8; WIDTH];
+        // pixels of each image row up to the right edge of the target
+        let visible = (target.x + target.width - x) as usize;
+        let columns = (target.x - x) as usize..visible;
+        let rows = target.y as u32..target.y as u32 + target.height as u32;
+        let mut remaining = target.height;
+        for i in 0..header.height {
+            header
+                .format
+                .read_row(input, header.width as usize, &mut row[..visible])?;
+            let row_y = match header.bottom_up {
+                true => y as u32 + (header.height - 1 - i) as u32,
+                false => y as u32 + i as u32,
+            };
+            if !rows.contains(&row_y) {
+                continue;
+            }
+            for column in columns.clone() {
+                let pixel_x = x + column as u16;
+                let color = match &header.format {
+                    Format::Native { transparent, .. } => match *transparent {
+                        Some(key) if row[column] == key => continue,
+                        _ => row[column] >> (4 - D::BITS),
+                    },
+                    // the stream order keeps the diffusion going for bottom-up images
+                    _ => ditherer.quantise::<D>(pixel_x, i, row[column], curve),
+                };
+                framebuffer::write_pixel::<D>(buffer, pixel_x as usize, row_y as usize, color);
+            }
+            remaining -= 1;
+            if remaining == 0 {
+                break;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Buffered reader of the image stream.
+struct Input<'r, R: Read> {
+    reader: &'r mut R,
+    buffer: [u8; BUFFER_SIZE],
+    pos: usize,
+    len: usize,
+}
+
+impl<R: Read> Input<'_, R> {
+    /// Reads the next bytes into the buffer, a stream which ends early is
+    /// treated as corrupted image.
+    fn fill(&mut self) -> Result<()> {
+        let len = self
+            .reader
+            .read(&mut self.buffer)
+            .map_err(|err| Error::Io(err.kind()))?;
+        if len == 0 {
+            return Err(Error::InvalidEncoding);
+        }
+        self.pos = 0;
+        self.len = len;
+        Ok(())
+    }
+
+    fn byte(&mut self) -> Result<u8> {
+        if self.pos == self.len {
+            self.fill()?;
+        }
+        self.pos += 1;
+        Ok(self.buffer[self.pos - 1])
+    }
+
+    fn read(&mut self, out: &mut [u8]) -> Result<()> {
+        for byte in out {
+            *byte = self.byte()?;
+        }
+        Ok(())
+    }
+
+    fn skip(&mut self, mut len: usize) -> Result<()> {
+        while len > 0 {
+            if self.pos == self.len {
+                self.fill()?;
+            }
+            let skipped = len.min(self.len - self.pos);
+            self.pos += skipped;
+            len -= skipped;
+        }
+        Ok(())
+    }
+
+    fn u16(&mut self) -> Result<u16> {
+        let mut bytes = [0; 2];
+        self.read(&mut bytes)?;
+        Ok(u16::from_le_bytes(bytes))
+    }
+
+    fn u32(&mut self) -> Result<u32> {
+        let mut bytes = [0; 4];
+        self.read(&mut bytes)?;
+        Ok(u32::from_le_bytes(bytes))
+    }
+}
+
+/// Size and format of an image.
+struct Header {
+    width: u16,
+    height: u16,
+    /// The last row is stored first.
+    bottom_up: bool,
+    format: Format,
+}
+
+/// Pixel data of an image.
+#[allow(clippy::large_enum_variant)]
+enum Format {
+    /// 8-bit gray values scaled by `255 / max`.
+    Pgm { max: u8 },
+    /// Rows padded to `stride` bytes, images with up to 8 bits per pixel
+    /// store the gray values of their colors in `palette`.
+    Bmp {
+        bits: u16,
+        stride: usize,
+        palette: [u8; 256],
+    },
+    /// 4bpp colors, `rle` tracks the current token of compressed images.
+    Native {
+        transparent: Option<u8>,
+        rle: Option<Rle>,
+    },
+}
+
+impl Header {
+    fn parse<R: Read>(input: &mut Input<'_, R>) -> Result<Header> {
+        let mut magic = [0; 2];
+        input.read(&mut magic)?;
+        match &magic {
+            b"P5" => Header::parse_pgm(input),
+            b"BM" => Header::parse_bmp(input),
+            b"EP" => Header::parse_native(input),
+            _ => Err(Error::UnsupportedFormat),
+        }
+    }
+
+    fn parse_pgm<R: Read>(input: &mut Input<'_, R>) -> Result<Header> {
+        let width = pgm_number(input)?;
+        let height = pgm_number(input)?;
+        let max = pgm_number(input)?;
+        if max == 0 {
+            return Err(Error::InvalidEncoding);
+        }
+        // 16-bit samples are not supported
+        let max = u8::try_from(max).map_err(|_| Error::UnsupportedFormat)?;
+        Ok(Header {
+            width: dimension(width)?,
+            height: dimension(height)?,
+            bottom_up: false,
+            format: Format::Pgm { max },
+        })
+    }
+
+    fn parse_bmp<R: Read>(input: &mut Input<'_, R>) -> Result<Header> {
+        // file size and reserved fields
+        input.skip(8)?;
+        let offset = input.u32()? as usize;
+        let info_size = input.u32()? as usize;
+        if info_size < BMP_INFO_HEADER_SIZE {
+            return Err(Error::UnsupportedFormat);
+        }
+        let width = input.u32()? as i32;
+        let height = input.u32()? as i32;
+        let _planes = input.u16()?;
+        let bits = input.u16()?;
+        let compression = input.u32()?;
+        // image size and resolution
+        input.skip(12)?;
+        let colors = input.u32()? as usize;
+        // huge header sizes overflow on 32-bit targets
+        input.skip(
+            (info_size - BMP_INFO_HEADER_SIZE)
+                .checked_add(4)
+                .ok_or(Error::InvalidEncoding)?,
+        )?;
+        if compression != 0 || !matches!(bits, 1 | 4 | 8 | 24 | 32) {
+            return Err(Error::UnsupportedFormat);
+        }
+        if width <= 0 || height == 0 {
+            return Err(Error::InvalidEncoding);
+        }
+
+        let mut palette = [0u8; 256];
+        let colors = match (bits, colors) {
+            (24 | 32, _) => 0,
+            (_, 0) => 1 << bits,
+            (_, colors) => colors,
+        };
+        for entry in palette.get_mut(..colors).ok_or(Error::InvalidEncoding)? {
+            let mut color = [0; 4];
+            input.read(&mut color)?;
+            let [blue, green, red, _] = color;
+            *entry = luma(red, green, blue);
+        }
+        let header_size = colors
+            .checked_mul(4)
+            .and_then(|size| size.checked_add(info_size))
+            .and_then(|size| size.checked_add(BMP_FILE_HEADER_SIZE))
+            .ok_or(Error::InvalidEncoding)?;
+        input.skip(
+            offset
+                .checked_sub(header_size)
+                .ok_or(Error::InvalidEncoding)?,
+        )?;
+
+        let width = width.unsigned_abs();
+        Ok(Header {
+            width: dimension(width)?,
+            height: dimension(height.unsigned_abs())?,
+            bottom_up: height > 0,
+            format: Format::Bmp {
+                bits,
+                stride: (width as usize * bits as usize).div_ceil(32) * 4,
+                palette,
+            },
+        })
+    }
+
+    fn parse_native<R: Read>(input: &mut Input<'_, R>) -> Result<Header> {
+        let mut header = [0; Image4bpp::HEADER_SIZE];
+        header[..2].copy_from_slice(b"EP");
+        input.read(&mut header[2..])?;
+        let image = Image4bpp::parse_header(&header)?;
+        Ok(Header {
+            width: image.width(),
+            height: image.height(),
+            bottom_up: false,
+            format: Format::Native {
+                transparent: image.transparent(),
+                rle: image.is_compressed().then_some(Rle {
+                    remaining: 0,
+                    literal: false,
+                    value: 0,
+                }),
+            },
+        })
+    }
+}
+
+impl Format {
+    /// Reads a row of `width` pixels and stores the first `row.len()` of
+    /// them, either as gray value or as 4bpp color of native images.
+    fn read_row<R: Read>(
+        &mut self,
+        input: &mut Input<'_, R>,
+        width: usize,
+        row: &mut [u8],
+    ) -> Result<()> {
+        match self {
+            Format::Pgm { max } => {
+                for x in 0..width {
+                    let value = input.byte()?.min(*max);
+                    if let Some(pixel) = row.get_mut(x) {
+                        *pixel = (value as u16 * 0xFF / *max as u16) as u8;
+                    }
+                }
+            }
+            Format::Bmp {
+                bits,
+                stride,
+                palette,
+            } => {
+                let bits = *bits as usize;
+                let mut byte = 0;
+                for x in 0..width {
+                    let value = match bits {
+                        24 | 32 => {
+                            let mut color = [0; 4];
+                            input.read(&mut color[..bits / 8])?;
+                            let [blue, green, red, _] = color;
+                            luma(red, green, blue)
+                        }
+                        _ => {
+                            // the left pixel is stored in the most significant bits
+                            let index = (x * bits) % 8;
+                            if index == 0 {
+                                byte = input.byte()?;
+                            }
+                            let color = (byte << index) >> (8 - bits);
+                            palette[color as usize]
+                        }
+                    };
+                    if let Some(pixel) = row.get_mut(x) {
+                        *pixel = value;
+                    }
+                }
+                input.skip(*stride - (width * bits).div_ceil(8))?;
+            }
+            Format::Native { rle, .. } => {
+                for x in (0..width).step_by(2) {
+                    let byte = match rle {
+                        Some(rle) => rle.byte(input)?,
+                        None => input.byte()?,
+                    };
+                    for (pixel, value) in row.iter_mut().skip(x).zip([byte & 0x0F, byte >> 4]) {
+                        *pixel = value;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Current token of [compressed](codec) native image data.
+struct Rle {
+    remaining: u32,
+    literal: bool,
+    value: u8,
+}
+
+impl Rle {
+    fn byte<R: Read>(&mut self, input: &mut Input<'_, R>) -> Result<u8> {
+        if self.remaining == 0 {
+            let header = codec::read_varint(|| input.byte())?;
+            self.remaining = (header >> 1) + 1;
+            self.literal = header & 1 == 1;
+            if !self.literal {
+                self.value = input.byte()?;
+            }
+        }
+        self.remaining -= 1;
+        match self.literal {
+            true => input.byte(),
+            false => Ok(self.value),
+        }
+    }
+}
+
+/// Reads a decimal number of a PGM header, skipping leading whitespace and
+/// comments.
+fn pgm_number<R: Read>(input: &mut Input<'_, R>) -> Result<u32> {
+    let mut byte = input.byte()?;
+    loop {
+        match byte {
+            b'#' => while input.byte()? != b'\n' {},
+            byte if byte.is_ascii_whitespace() => {}
+            _ => break,
+        }
+        byte = input.byte()?;
+    }
+    let mut value = 0u32;
+    let mut digits = 0;
+    while byte.is_ascii_digit() {
+        value = value
+            .saturating_mul(10)
+            .saturating_add((byte - b'0') as u32);
+        digits += 1;
+        byte = input.byte()?;
+    }
+    // a single whitespace separates the header from the pixel data
+    if digits == 0 || !byte.is_ascii_whitespace() {
+        return Err(Error::InvalidEncoding);
+    }
+    Ok(value)
+}
+
+/// Converts the width or height of an image.
+fn dimension(value: u32) -> Result<u16> {
+    match u16::try_from(value) {
+        Ok(0) => Err(Error::InvalidEncoding),
+        Ok(value) => Ok(value),
+        Err(_) => Err(Error::UnsupportedFormat),
+    }
+}
+
+/// Gray value of a color, weighted like the ITU-R BT.601 luma.
+fn luma(red: u8, green: u8, blue: u8) -> u8 {
+    ((red as u32 * 77 + green as u32 * 150 + blue as u32 * 29) >> 8) as u8
+}
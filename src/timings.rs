@@ -0,0 +1,62 @@
+//! Timings used to drive the panel.
+//!
+//! The timings don't depend on the peripherals, so they are shared by
+//! [Display](crate::Display) and the [PanelConfig](crate::PanelConfig) blob
+//! and tested on the host.
+
+use crate::{Error, Result};
+
+/// Timings used to drive the panel. The defaults are tuned for a single
+/// panel at room temperature, other panel batches might require different
+/// values.
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Timings {
+    /// Output time of each frame of
+    /// [DrawMode::BlackOnWhite](crate::DrawMode::BlackOnWhite) and
+    /// [DrawMode::WhiteOnWhite](crate::DrawMode::WhiteOnWhite) updates.
+    pub contrast_cycles: [u16; 15],
+    /// Output time of each frame of
+    /// [DrawMode::WhiteOnBlack](crate::DrawMode::WhiteOnBlack) updates.
+    pub contrast_cycles_white: [u16; 15],
+    /// Number of frames drawn per flush, between 1 and
+    /// [Timings::MAX_FRAME_COUNT].
+    /// Fewer frames speed up flushing at the cost of the darkest (or
+    /// lightest) gray levels. Displays with fewer gray levels than 16 drive
+    /// the frames of several levels at once.
+    pub frame_count: u8,
+    /// Number of black/white cycles used to clear the screen.
+    pub clear_cycles: u16,
+    /// Output time of a row while clearing the screen.
+    pub clear_cycle_time: u16,
+}
+
+impl Timings {
+    /// Maximum number of frames per flush, one per gray level transition.
+    pub const MAX_FRAME_COUNT: u8 = 15;
+
+    /// Default timings of the driver.
+    pub const DEFAULT: Timings = Timings {
+        contrast_cycles: [
+            30, 30, 20, 20, 30, 30, 30, 40, 40, 50, 50, 50, 100, 200, 300,
+        ],
+        contrast_cycles_white: [10, 10, 8, 8, 8, 8, 8, 10, 10, 15, 15, 20, 20, 100, 300],
+        frame_count: Self::MAX_FRAME_COUNT,
+        clear_cycles: 4,
+        clear_cycle_time: 50,
+    };
+
+    pub(crate) fn validate(&self) -> Result<()> {
+        // without frames every flush would silently do nothing
+        if self.frame_count == 0 || self.frame_count > Self::MAX_FRAME_COUNT {
+            return Err(Error::InvalidTimings);
+        }
+        Ok(())
+    }
+}
+
+impl Default for Timings {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
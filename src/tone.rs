@@ -0,0 +1,100 @@
+//! Tone curve of the panel.
+//!
+//! The 16 gray levels of the ED047TC1 are not perceptually evenly spaced, the
+//! darker levels are much closer together than the lighter ones. A
+//! [ToneCurve] describes the lightness of every gray level and is used to map
+//! 8-bit input to the levels which match it best.
+//!
+//! Only the paths which quantise 8-bit input use the curve: the
+//! [DitheringDrawTarget](crate::DitheringDrawTarget), PGM and BMP images drawn
+//! by [Display::draw_stream](crate::Display::draw_stream) and the
+//! `epd47-convert` tool. Pixel values set directly, e.g. `Gray4` colors drawn
+//! through `embedded-graphics` or
+//! [Display::set_pixel](crate::Display::set_pixel), are stored as they are, and
+//! the lookup tables driving the panel don't depend on the curve.
+
+use crate::{depth::PixelDepth, Error, Result};
+
+/// Lightness of the 16 gray levels of the panel.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct ToneCurve {
+    levels: [u8; 16],
+}
+
+impl ToneCurve {
+    /// Evenly spaced gray levels, i.e. no correction.
+    pub const LINEAR: ToneCurve = ToneCurve {
+        levels: [
+            0, 17, 34, 51, 68, 85, 102, 119, 136, 153, 170, 187, 204, 221, 238, 255,
+        ],
+    };
+
+    /// Curve used by default. No measured curve of the panel is available
+    /// yet, so the levels are assumed to be evenly spaced like
+    /// [ToneCurve::LINEAR]. Run
+    /// [Display::calibrate_grayscale](crate::Display::calibrate_grayscale) to
+    /// get the curve of a panel.
+    pub const DEFAULT: ToneCurve = Self::LINEAR;
+
+    /// Creates a curve from the lightness of every gray level, e.g. measured
+    /// by [Display::calibrate_grayscale](crate::Display::calibrate_grayscale).
+    ///
+    /// Returns [Error::InvalidToneCurve] if the lightness decreases at any
+    /// level or black and white are equally light.
+    pub fn new(levels: [u8; 16]) -> Result<Self> {
+        if levels.windows(2).any(|pair| pair[0] > pair[1]) || levels[0] == levels[15] {
+            return Err(Error::InvalidToneCurve);
+        }
+        Ok(ToneCurve { levels })
+    }
+
+    /// Lightness of every gray level.
+    pub fn levels(&self) -> &[u8; 16] {
+        &self.levels
+    }
+
+    /// Returns the gray level whose lightness matches the 8-bit gray value
+    /// best.
+    pub fn level(&self, luma: u8) -> u8 {
+        self.nearest::<crate::Bpp4>(self.target(luma as i32))
+    }
+
+    /// Scales an 8-bit gray value to the lightness range of the curve.
+    pub(crate) fn target(&self, luma: i32) -> i32 {
+        let (black, white) = (self.levels[0] as i32, self.levels[15] as i32);
+        black + luma * (white - black) / 255
+    }
+
+    /// Lightness of a pixel value of the given depth.
+    pub(crate) fn lightness<D: PixelDepth>(&self, value: u8) -> i32 {
+        self.levels[value as usize * 15 / D::MAX_COLOR as usize] as i32
+    }
+
+    /// Pixel value whose lightness is closest to `lightness`.
+    pub(crate) fn nearest<D: PixelDepth>(&self, lightness: i32) -> u8 {
+        (0..=D::MAX_COLOR)
+            .min_by_key(|&value| (self.lightness::<D>(value) - lightness).abs())
+            .unwrap_or(0)
+    }
+
+    /// Lightest pixel value not lighter than `lightness` and the position of
+    /// `lightness` between this and the next lighter value, scaled to 0 - 255.
+    pub(crate) fn bracket<D: PixelDepth>(&self, lightness: i32) -> (u8, i32) {
+        let value = (0..=D::MAX_COLOR)
+            .rev()
+            .find(|&value| self.lightness::<D>(value) <= lightness)
+            .unwrap_or(0);
+        if value == D::MAX_COLOR {
+            return (value, 0);
+        }
+        let (low, high) = (self.lightness::<D>(value), self.lightness::<D>(value + 1));
+        (value, (lightness - low).max(0) * 255 / (high - low))
+    }
+}
+
+impl Default for ToneCurve {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
@@ -0,0 +1,186 @@
+//! Driver for the TI TPS65185 e-paper power management IC.
+//!
+//! The TPS65185 generates the panel rails, the VCOM voltage and reads the
+//! panel thermistor. It is used on newer LilyGo and epdiy boards and is
+//! controlled over I2C. The WAKEUP pin of the PMIC has to be pulled high
+//! before the registers can be accessed.
+//!
+//! The driver only depends on [`embedded-hal`] so it can be used with any I2C
+//! implementation.
+//!
+//! [`embedded-hal`]: https://docs.rs/embedded-hal/
+
+use embedded_hal::{
+    delay::DelayNs,
+    i2c::{Error as _, I2c},
+};
+
+use crate::{Error, Result};
+
+/// Default I2C address of the TPS65185.
+pub const DEFAULT_ADDRESS: u8 = 0x68;
+
+const REG_TMST_VALUE: u8 = 0x00;
+const REG_ENABLE: u8 = 0x01;
+const REG_VCOM1: u8 = 0x03;
+const REG_VCOM2: u8 = 0x04;
+const REG_INT1: u8 = 0x07;
+const REG_TMST1: u8 = 0x0D;
+const REG_PG: u8 = 0x0F;
+const REG_REVID: u8 = 0x10;
+
+const ENABLE_ACTIVE: u8 = 1 << 7;
+const ENABLE_STANDBY: u8 = 1 << 6;
+const ENABLE_RAILS: u8 = 0x3F;
+
+const VCOM2_PROG: u8 = 1 << 6;
+
+const INT1_PRGC: u8 = 1 << 0;
+
+const TMST1_READ_THERM: u8 = 1 << 7;
+const TMST1_CONV_END: u8 = 1 << 5;
+
+// Bits of the PG register (0x0F), see "PG Register" in the register maps of
+// the TPS65185 datasheet. Bits 2 and 0 are not used, the layout matches the
+// undervoltage flags of INT2.
+const PG_VB: u8 = 1 << 7;
+const PG_VDDH: u8 = 1 << 6;
+const PG_VN: u8 = 1 << 5;
+const PG_VPOS: u8 = 1 << 4;
+const PG_VEE: u8 = 1 << 3;
+const PG_VNEG: u8 = 1 << 1;
+
+/// Power good bits of all rails, `0xFA` like the reference driver of epdiy.
+const PG_ALL: u8 = PG_VB | PG_VDDH | PG_VN | PG_VPOS | PG_VEE | PG_VNEG;
+
+/// Number of polls before giving up on the power good or conversion flags.
+const POLL_RETRIES: u32 = 100;
+/// Time between two polls in microseconds.
+const POLL_INTERVAL_US: u32 = 1000;
+
+/// Highest VCOM value the PMIC can output in millivolts.
+pub const VCOM_MAX_MV: u16 = 5110;
+
+pub struct Tps65185<I2C> {
+    i2c: I2C,
+    address: u8,
+}
+
+impl<I2C> Tps65185<I2C>
+where
+    I2C: I2c,
+{
+    /// Create a new driver using the [DEFAULT_ADDRESS].
+    pub fn new(i2c: I2C) -> Self {
+        Self::with_address(i2c, DEFAULT_ADDRESS)
+    }
+
+    /// Create a new driver for a PMIC with a non-default address.
+    pub fn with_address(i2c: I2C, address: u8) -> Self {
+        Tps65185 { i2c, address }
+    }
+
+    /// Release the underlying I2C bus.
+    pub fn release(self) -> I2C {
+        self.i2c
+    }
+
+    /// Read the revision id of the chip.
+    pub fn revision(&mut self) -> Result<u8> {
+        self.read_register(REG_REVID)
+    }
+
+    /// Power up all panel rails and wait until the PMIC reports them as good.
+    ///
+    /// Returns [Error::PowerGood] if the rails do not come up in time.
+    pub fn power_up(&mut self, delay: &mut impl DelayNs) -> Result<()> {
+        self.write_register(REG_ENABLE, ENABLE_ACTIVE | ENABLE_RAILS)?;
+        for _ in 0..POLL_RETRIES {
+            if self.power_good()? {
+                return Ok(());
+            }
+            delay.delay_us(POLL_INTERVAL_US);
+        }
+        Err(Error::PowerGood)
+    }
+
+    /// Power down all panel rails using the power down sequence of the PMIC.
+    pub fn power_down(&mut self) -> Result<()> {
+        self.write_register(REG_ENABLE, ENABLE_STANDBY | ENABLE_RAILS)
+    }
+
+    /// Returns `true` if all panel rails are within their limits.
+    pub fn power_good(&mut self) -> Result<bool> {
+        Ok(self.read_register(REG_PG)? & PG_ALL == PG_ALL)
+    }
+
+    /// Read the configured VCOM voltage in millivolts. The actual voltage is
+    /// negative.
+    pub fn vcom(&mut self) -> Result<u16> {
+        let low = self.read_register(REG_VCOM1)? as u16;
+        let high = (self.read_register(REG_VCOM2)? & 0x01) as u16;
+        Ok(((high << 8) | low) * 10)
+    }
+
+    /// Set the VCOM voltage in millivolts, the value is rounded down to the
+    /// 10mV resolution of the PMIC. The actual voltage is negative, i.e. pass
+    /// `1560` for -1.56V.
+    ///
+    /// Returns [Error::InvalidVcom] if the value exceeds [VCOM_MAX_MV].
+    pub fn set_vcom(&mut self, millivolts: u16) -> Result<()> {
+        if millivolts > VCOM_MAX_MV {
+            return Err(Error::InvalidVcom);
+        }
+        let value = millivolts / 10;
+        self.write_register(REG_VCOM1, (value & 0xFF) as u8)?;
+        let vcom2 = self.read_register(REG_VCOM2)?;
+        self.write_register(REG_VCOM2, (vcom2 & 0xFE) | ((value >> 8) as u8 & 0x01))
+    }
+
+    /// Set the VCOM voltage in millivolts and program it into the EEPROM of
+    /// the PMIC, so it survives power cycles.
+    ///
+    /// Returns [Error::Timeout] if programming does not finish in time.
+    pub fn program_vcom(&mut self, millivolts: u16, delay: &mut impl DelayNs) -> Result<()> {
+        self.set_vcom(millivolts)?;
+        // reading INT1 clears pending interrupt flags
+        self.read_register(REG_INT1)?;
+        let vcom2 = self.read_register(REG_VCOM2)?;
+        self.write_register(REG_VCOM2, vcom2 | VCOM2_PROG)?;
+        for _ in 0..POLL_RETRIES {
+            if self.read_register(REG_INT1)? & INT1_PRGC != 0 {
+                return Ok(());
+            }
+            delay.delay_us(POLL_INTERVAL_US);
+        }
+        Err(Error::Timeout)
+    }
+
+    /// Read the panel temperature in degrees celsius.
+    ///
+    /// Returns [Error::Timeout] if the conversion does not finish in time.
+    pub fn temperature(&mut self, delay: &mut impl DelayNs) -> Result<i8> {
+        self.write_register(REG_TMST1, TMST1_READ_THERM)?;
+        for _ in 0..POLL_RETRIES {
+            if self.read_register(REG_TMST1)? & TMST1_CONV_END != 0 {
+                return Ok(self.read_register(REG_TMST_VALUE)? as i8);
+            }
+            delay.delay_us(POLL_INTERVAL_US);
+        }
+        Err(Error::Timeout)
+    }
+
+    fn read_register(&mut self, register: u8) -> Result<u8> {
+        let mut buf = [0u8; 1];
+        self.i2c
+            .write_read(self.address, &[register], &mut buf)
+            .map_err(|err| Error::I2c(err.kind()))?;
+        Ok(buf[0])
+    }
+
+    fn write_register(&mut self, register: u8, value: u8) -> Result<()> {
+        self.i2c
+            .write(self.address, &[register, value])
+            .map_err(|err| Error::I2c(err.kind()))
+    }
+}
@@ -1,6 +1,57 @@
 #![allow(unused)]
 pub struct Phases {}
 
+/// Temperature intervals in degrees celsius covered by the waveform ranges
+/// below (`ed047tc2_intervals[5..12]`).
+const TEMPERATURE_INTERVALS: [(i8, i8); 7] = [
+    (15, 18),
+    (18, 21),
+    (21, 24),
+    (24, 27),
+    (27, 30),
+    (30, 33),
+    (33, 38),
+];
+
+/// Number of phases of the mode 1 waveform for each temperature range.
+const MODE_1_PHASES: [u16; 7] = [
+    EPD_WP_ED047TC2_1_5_DATA.len() as u16,
+    EPD_WP_ED047TC2_1_6_DATA.len() as u16,
+    EPD_WP_ED047TC2_1_7_DATA.len() as u16,
+    EPD_WP_ED047TC2_1_8_DATA.len() as u16,
+    EPD_WP_ED047TC2_1_9_DATA.len() as u16,
+    EPD_WP_ED047TC2_1_10_DATA.len() as u16,
+    EPD_WP_ED047TC2_1_11_DATA.len() as u16,
+];
+
+/// Temperature range used when no temperature has been provided.
+pub(crate) const DEFAULT_TEMPERATURE_RANGE: usize = 3;
+
+/// Selects the waveform temperature range for the given temperature.
+/// Temperatures outside the covered intervals are clamped to the closest
+/// range.
+pub(crate) fn temperature_range(temperature: i8) -> usize {
+    TEMPERATURE_INTERVALS
+        .iter()
+        .position(|&(_, max)| temperature < max)
+        .unwrap_or(TEMPERATURE_INTERVALS.len() - 1)
+}
+
+/// Scales an output time of the default temperature range to the given range
+/// based on the phase count of the mode 1 waveform. Colder panels need longer
+/// driving times.
+///
+/// This is an approximation, not the vendor waveform: the vendor tables also
+/// change which pixels are driven in each phase, while the driver keeps its
+/// own frame sequence and only stretches the time of each frame by the ratio
+/// of the phase counts. It assumes all phases take the same time. Results
+/// saturate at `u16::MAX`.
+pub(crate) fn scale_time(time: u16, range: usize) -> u16 {
+    let scaled =
+        time as u32 * MODE_1_PHASES[range] as u32 / MODE_1_PHASES[DEFAULT_TEMPERATURE_RANGE] as u32;
+    scaled.min(u16::MAX as u32) as u16
+}
+
 const EPD_WP_ED047TC2_1_5_DATA: [[[u8; 4]; 16]; 25] = [
     [
         [0x00, 0x00, 0x00, 0x01],
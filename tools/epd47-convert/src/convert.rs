@@ -0,0 +1,191 @@
+//! Loading, scaling and quantisation of images.
+
+use std::path::Path;
+
+use ::image::{imageops::FilterType, GrayAlphaImage, RgbaImage};
+use anyhow::{bail, Context};
+use resvg::{tiny_skia, usvg};
+
+use crate::{
+    depth::PixelDepth,
+    dither::{self, Ditherer},
+    framebuffer,
+    tone::ToneCurve,
+};
+
+/// Dithering algorithm, same as the `Dithering` of the driver.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum)]
+pub enum Dithering {
+    /// Picks the closest gray level.
+    None,
+    /// Ordered dithering using a 4x4 Bayer matrix.
+    Bayer,
+    /// Floyd–Steinberg error diffusion.
+    FloydSteinberg,
+    /// Atkinson error diffusion.
+    Atkinson,
+}
+
+impl Dithering {
+    /// Dithering of the driver, `None` if the closest gray level is picked.
+    fn driver(self) -> Option<dither::Dithering> {
+        match self {
+            Dithering::None => None,
+            Dithering::Bayer => Some(dither::Dithering::Bayer),
+            Dithering::FloydSteinberg => Some(dither::Dithering::FloydSteinberg),
+            Dithering::Atkinson => Some(dither::Dithering::Atkinson),
+        }
+    }
+}
+
+/// Size of the converted image.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Size {
+    /// Width in pixels, derived from the height if `None`.
+    pub width: Option<u32>,
+    /// Height in pixels, derived from the width if `None`.
+    pub height: Option<u32>,
+    /// Scale to exactly the given size instead of fitting the image into it.
+    pub stretch: bool,
+}
+
+impl Size {
+    /// Returns the size of an image with the given dimensions after scaling.
+    pub fn apply(&self, width: u32, height: u32) -> (u32, u32) {
+        let scale = |value: u32, from: u32, to: u32| {
+            ((value as u64 * to as u64 + from as u64 / 2) / from as u64).max(1) as u32
+        };
+        match (self.width, self.height) {
+            (None, None) => (width, height),
+            (Some(w), None) => (w, scale(height, width, w)),
+            (None, Some(h)) => (scale(width, height, h), h),
+            (Some(w), Some(h)) if self.stretch => (w, h),
+            (Some(w), Some(h)) => match width as u64 * h as u64 > height as u64 * w as u64 {
+                true => (w, scale(height, width, w)),
+                false => (scale(width, height, h), h),
+            },
+        }
+    }
+}
+
+/// Loads a PNG, JPEG or SVG image and scales it. SVG images are rendered at
+/// the requested size.
+pub fn load(path: &Path, size: Size) -> anyhow::Result<RgbaImage> {
+    let is_svg = path
+        .extension()
+        .is_some_and(|extension| extension.eq_ignore_ascii_case("svg"));
+    if is_svg {
+        let data = std::fs::read(path).with_context(|| format!("reading {}", path.display()))?;
+        return render_svg(&data, size);
+    }
+    let image = ::image::open(path)
+        .with_context(|| format!("decoding {}", path.display()))?
+        .into_rgba8();
+    let (width, height) = size.apply(image.width(), image.height());
+    if (width, height) == image.dimensions() {
+        return Ok(image);
+    }
+    Ok(::image::imageops::resize(
+        &image,
+        width,
+        height,
+        FilterType::Lanczos3,
+    ))
+}
+
+fn render_svg(data: &[u8], size: Size) -> anyhow::Result<RgbaImage> {
+    let tree = usvg::Tree::from_data(data, &usvg::Options::default())?;
+    let original = tree.size().to_int_size();
+    let (width, height) = size.apply(original.width(), original.height());
+    let Some(mut pixmap) = tiny_skia::Pixmap::new(width, height) else {
+        bail!("invalid image size {width}x{height}");
+    };
+    let transform = tiny_skia::Transform::from_scale(
+        width as f32 / tree.size().width(),
+        height as f32 / tree.size().height(),
+    );
+    resvg::render(&tree, transform, &mut pixmap.as_mut());
+    let pixels = pixmap
+        .pixels()
+        .iter()
+        .flat_map(|pixel| {
+            let color = pixel.demultiply();
+            [color.red(), color.green(), color.blue(), color.alpha()]
+        })
+        .collect();
+    Ok(RgbaImage::from_raw(width, height, pixels).expect("pixmap matches the image size"))
+}
+
+/// Converts the image to grayscale. Without a transparent color the image is
+/// blended with the `background` gray value, otherwise pixels which are
+/// mostly transparent are marked using an alpha of 0.
+pub fn grayscale(image: &RgbaImage, background: u8, transparent: bool) -> GrayAlphaImage {
+    let mut gray = ::image::DynamicImage::ImageRgba8(image.clone()).into_luma_alpha8();
+    for pixel in gray.pixels_mut() {
+        let [luma, alpha] = pixel.0;
+        pixel.0 = match transparent {
+            true if alpha < 0x80 => [background, 0],
+            true => [luma, 0xFF],
+            false => {
+                let blended =
+                    (luma as u32 * alpha as u32 + background as u32 * (255 - alpha as u32)) / 255;
+                [blended as u8, 0xFF]
+            }
+        };
+    }
+    gray
+}
+
+/// Quantises the image to the gray levels of the depth `D` with the
+/// dithering of the driver and returns the 4bpp color of every pixel.
+/// Transparent pixels are set to the `transparent` color, which is not used by
+/// any other pixel.
+///
+/// Error diffusion keeps the error of a single row of the screen, so the
+/// image can't be wider than the screen.
+pub fn quantise<D: PixelDepth>(
+    image: &GrayAlphaImage,
+    curve: &ToneCurve,
+    dithering: Dithering,
+    transparent: Option<u8>,
+) -> anyhow::Result<Vec<u8>> {
+    let width = image.width() as usize;
+    let diffusion = matches!(dithering, Dithering::FloydSteinberg | Dithering::Atkinson);
+    if diffusion && width > framebuffer::WIDTH as usize {
+        bail!(
+            "error diffusion supports images up to {} pixels wide",
+            framebuffer::WIDTH
+        );
+    }
+    let mut ditherer = dithering.driver().map(Ditherer::new);
+    let mut colors = Vec::with_capacity(image.len());
+    for (i, pixel) in image.pixels().enumerate() {
+        let [luma, alpha] = pixel.0;
+        let (x, y) = ((i % width) as u16, (i / width) as u16);
+        let value = match &mut ditherer {
+            Some(ditherer) => ditherer.quantise::<D>(x, y, luma, curve),
+            None => curve.nearest::<D>(curve.target(luma as i32)),
+        };
+        let color = (value as usize * 15 / D::MAX_COLOR as usize) as u8;
+        colors.push(match transparent {
+            Some(key) if alpha == 0 => key,
+            // the transparent color is reserved, use the closest other one
+            Some(key) if color == key && key == 0x0F => key - 1,
+            Some(key) if color == key => key + 1,
+            _ => color,
+        });
+    }
+    Ok(colors)
+}
+
+/// Packs 4bpp colors into rows of `(width + 1) / 2` bytes, the left pixel is
+/// stored in the lower nibble.
+pub fn pack(colors: &[u8], width: usize) -> Vec<u8> {
+    colors
+        .chunks(width)
+        .flat_map(|row| {
+            row.chunks(2)
+                .map(|pair| pair[0] | pair.get(1).unwrap_or(&0) << 4)
+        })
+        .collect()
+}
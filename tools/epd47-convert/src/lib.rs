@@ -0,0 +1,63 @@
+//! Conversion of images into the native 4bpp format of `lilygo-epd47`.
+//!
+//! The driver depends on `esp-hal` and can't be built for the host, so the
+//! image format, the codec, the tone curve and the dithering are compiled from
+//! the sources of the driver. This keeps the converter and the driver in sync.
+
+// blitting and drawing images are only used by the driver
+#[path = "../../../src/blit.rs"]
+#[allow(dead_code)]
+mod blit;
+#[path = "../../../src/codec.rs"]
+pub mod codec;
+#[path = "../../../src/depth.rs"]
+pub mod depth;
+#[path = "../../../src/dither.rs"]
+mod dither;
+// only the size of the screen is used
+#[path = "../../../src/framebuffer.rs"]
+#[allow(dead_code)]
+mod framebuffer;
+#[path = "../../../src/image.rs"]
+#[allow(dead_code)]
+pub mod image;
+#[path = "../../../src/tone.rs"]
+pub mod tone;
+
+pub mod convert;
+
+pub use depth::{Bpp1, Bpp4};
+
+/// Errors of the shared driver sources.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Error {
+    /// The provided pixel coordinates exceed the display boundary.
+    OutOfBounds,
+    /// The provided color exceeds the allowed range of 0x0 - 0x0F.
+    InvalidColor,
+    /// The provided output buffer is too small.
+    BufferTooSmall,
+    /// The provided encoded image data is corrupted.
+    InvalidEncoding,
+    /// The provided buffers differ in size.
+    SizeMismatch,
+    /// The provided tone curve is not monotonic.
+    InvalidToneCurve,
+}
+
+impl core::fmt::Display for Error {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Error::OutOfBounds => write!(f, "pixel coordinates exceed the display boundary"),
+            Error::InvalidColor => write!(f, "color exceeds the range of 0x0 - 0xF"),
+            Error::BufferTooSmall => write!(f, "output buffer is too small"),
+            Error::InvalidEncoding => write!(f, "encoded image data is corrupted"),
+            Error::SizeMismatch => write!(f, "buffer sizes do not match"),
+            Error::InvalidToneCurve => write!(f, "tone curve is not monotonic"),
+        }
+    }
+}
+
+impl core::error::Error for Error {}
+
+type Result<T> = core::result::Result<T, Error>;
@@ -0,0 +1,174 @@
+//! Converts PNG, JPEG and SVG images into the native 4bpp image format of
+//! `lilygo-epd47`.
+
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Context};
+use clap::Parser;
+use epd47_convert::{
+    convert::{self, Dithering, Size},
+    image::Image4bpp,
+    tone::ToneCurve,
+    Bpp1,
+    Bpp4,
+};
+
+/// Converts PNG, JPEG and SVG images into the native 4bpp image format of
+/// lilygo-epd47, which can be drawn using `Display::draw_image`.
+#[derive(Parser)]
+#[command(version)]
+struct Args {
+    /// Input image (PNG, JPEG or SVG).
+    input: PathBuf,
+    /// Output file, defaults to the input file with the extension `epi4`.
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+    /// Width in pixels, keeps the aspect ratio if no height is given.
+    #[arg(long)]
+    width: Option<u32>,
+    /// Height in pixels, keeps the aspect ratio if no width is given.
+    #[arg(long)]
+    height: Option<u32>,
+    /// Scale to exactly the given width and height instead of fitting the
+    /// image into them.
+    #[arg(long)]
+    stretch: bool,
+    /// Dithering algorithm.
+    #[arg(long, value_enum, default_value_t = Dithering::FloydSteinberg)]
+    dither: Dithering,
+    /// Quantise to black and white instead of 16 gray levels.
+    #[arg(long)]
+    black_white: bool,
+    /// Tone curve of the panel: `linear` or the 16 comma separated lightness
+    /// values of a calibrated panel.
+    #[arg(long, default_value = "linear", value_parser = parse_tone_curve)]
+    tone_curve: ToneCurve,
+    /// Color (0 - 15) of transparent pixels, which are not drawn. The color
+    /// is not used by any other pixel. Black and white images use 0 and 15,
+    /// so the color has to be between 1 and 14 with `--black-white`.
+    #[arg(long, value_parser = clap::value_parser!(u8).range(0..16))]
+    transparent: Option<u8>,
+    /// Gray value (0 - 255) transparent areas are blended with if no
+    /// transparent color is set.
+    #[arg(long, default_value_t = 0xFF)]
+    background: u8,
+    /// Store the pixel data uncompressed.
+    #[arg(long)]
+    uncompressed: bool,
+    /// Also write a Rust source file which embeds the image using
+    /// `include_bytes!`.
+    #[arg(long)]
+    rust: Option<PathBuf>,
+    /// Name of the constant in the Rust source file, derived from the input
+    /// file by default.
+    #[arg(long)]
+    name: Option<String>,
+}
+
+fn parse_tone_curve(value: &str) -> Result<ToneCurve, String> {
+    if value == "linear" {
+        return Ok(ToneCurve::LINEAR);
+    }
+    let levels = value
+        .split(',')
+        .map(|level| level.trim().parse::<u8>())
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|err| err.to_string())?;
+    let levels = levels
+        .try_into()
+        .map_err(|levels: Vec<u8>| format!("expected 16 values, got {}", levels.len()))?;
+    ToneCurve::new(levels).map_err(|err| err.to_string())
+}
+
+fn main() -> anyhow::Result<()> {
+    let args = Args::parse();
+    if args.black_white && matches!(args.transparent, Some(0 | 0x0F)) {
+        bail!("the transparent color has to be between 1 and 14 with --black-white");
+    }
+    let size = Size {
+        width: args.width,
+        height: args.height,
+        stretch: args.stretch,
+    };
+    let image = convert::load(&args.input, size)?;
+    let (width, height) = image.dimensions();
+    let (Ok(width), Ok(height)) = (u16::try_from(width), u16::try_from(height)) else {
+        bail!("image size {width}x{height} exceeds the supported size");
+    };
+
+    let gray = convert::grayscale(&image, args.background, args.transparent.is_some());
+    let colors = match args.black_white {
+        true => convert::quantise::<Bpp1>(&gray, &args.tone_curve, args.dither, args.transparent)?,
+        false => convert::quantise::<Bpp4>(&gray, &args.tone_curve, args.dither, args.transparent)?,
+    };
+    let data = convert::pack(&colors, width as usize);
+
+    let mut out = vec![0u8; Image4bpp::HEADER_SIZE + data.len() * 2];
+    let mut len = Image4bpp::encode(
+        width,
+        height,
+        &data,
+        args.transparent,
+        !args.uncompressed,
+        &mut out,
+    )?;
+    // incompressible images are stored as they are
+    if len > Image4bpp::HEADER_SIZE + data.len() {
+        len = Image4bpp::encode(width, height, &data, args.transparent, false, &mut out)?;
+    }
+    let output = args
+        .output
+        .unwrap_or_else(|| args.input.with_extension("epi4"));
+    std::fs::write(&output, &out[..len])
+        .with_context(|| format!("writing {}", output.display()))?;
+    println!(
+        "{}: {width}x{height}, {len} bytes ({} uncompressed)",
+        output.display(),
+        Image4bpp::HEADER_SIZE + data.len()
+    );
+
+    if let Some(rust) = args.rust {
+        let name = args.name.unwrap_or_else(|| constant_name(&args.input));
+        write_rust(&rust, &output, &name, width, height)?;
+        println!("{}: {name}", rust.display());
+    }
+    Ok(())
+}
+
+/// Derives the name of a constant from a file name, e.g. `logo-large.png`
+/// turns into `LOGO_LARGE`.
+fn constant_name(path: &Path) -> String {
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let name: String = stem
+        .chars()
+        .map(|c| match c.is_ascii_alphanumeric() {
+            true => c.to_ascii_uppercase(),
+            false => '_',
+        })
+        .collect();
+    match name.starts_with(|c: char| c.is_ascii_digit()) || name.is_empty() {
+        true => format!("IMAGE_{name}"),
+        false => name,
+    }
+}
+
+fn write_rust(
+    path: &Path,
+    image: &Path,
+    name: &str,
+    width: u16,
+    height: u16,
+) -> anyhow::Result<()> {
+    // include relative to the source file if both are in the same directory
+    let include = match path.parent() == image.parent() {
+        true => PathBuf::from(image.file_name().unwrap_or_default()),
+        false => std::fs::canonicalize(image)?,
+    };
+    let source = format!(
+        "// Generated by epd47-convert, do not edit.\n\n\
+         /// {width}x{height} pixels, draw using `Image4bpp::new({name})`.\n\
+         pub const {name}: &[u8] = include_bytes!({:?});\n",
+        include.display().to_string()
+    );
+    std::fs::write(path, source).with_context(|| format!("writing {}", path.display()))
+}
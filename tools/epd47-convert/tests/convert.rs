@@ -0,0 +1,131 @@
+use epd47_convert::{
+    codec,
+    convert::{grayscale, pack, quantise, Dithering, Size},
+    image::Image4bpp,
+    tone::ToneCurve,
+    Bpp1,
+    Bpp4,
+};
+use image::{GrayAlphaImage, LumaA, Rgba, RgbaImage};
+
+fn size(width: Option<u32>, height: Option<u32>, stretch: bool) -> Size {
+    Size {
+        width,
+        height,
+        stretch,
+    }
+}
+
+/// Gray image of a single row with the given luma and alpha values.
+fn row(pixels: &[(u8, u8)]) -> GrayAlphaImage {
+    let mut image = GrayAlphaImage::new(pixels.len() as u32, 1);
+    for (pixel, &(luma, alpha)) in image.pixels_mut().zip(pixels) {
+        *pixel = LumaA([luma, alpha]);
+    }
+    image
+}
+
+#[test]
+fn size_keeps_aspect_ratio() {
+    assert_eq!(Size::default().apply(200, 100), (200, 100));
+    assert_eq!(size(Some(50), None, false).apply(200, 100), (50, 25));
+    assert_eq!(size(None, Some(50), false).apply(200, 100), (100, 50));
+    // rounded to the nearest pixel, but at least one
+    assert_eq!(size(Some(4), None, false).apply(3, 2), (4, 3));
+    assert_eq!(size(Some(5), None, false).apply(3, 2), (5, 3));
+    assert_eq!(size(Some(10), None, false).apply(1000, 1), (10, 1));
+    assert_eq!(size(None, Some(10), false).apply(1, 1000), (1, 10));
+}
+
+#[test]
+fn size_fits_into_width_and_height() {
+    let fit = size(Some(100), Some(100), false);
+    assert_eq!(fit.apply(200, 100), (100, 50));
+    assert_eq!(fit.apply(100, 200), (50, 100));
+    assert_eq!(fit.apply(30, 30), (100, 100));
+    assert_eq!(size(Some(100), Some(100), true).apply(200, 100), (100, 100));
+}
+
+#[test]
+fn quantise_remaps_transparent_color() {
+    // black, gray level 7, white and a transparent pixel
+    let image = row(&[(0, 0xFF), (7 * 17, 0xFF), (0xFF, 0xFF), (0x80, 0)]);
+    let curve = ToneCurve::LINEAR;
+    let quantise = |key| quantise::<Bpp4>(&image, &curve, Dithering::None, key).unwrap();
+    assert_eq!(quantise(None), [0, 7, 15, 8]);
+    // the closest other color is used for pixels of the transparent color
+    assert_eq!(quantise(Some(0)), [1, 7, 15, 0]);
+    assert_eq!(quantise(Some(7)), [0, 8, 15, 7]);
+    assert_eq!(quantise(Some(15)), [0, 7, 14, 15]);
+    assert_eq!(quantise(Some(3)), [0, 7, 15, 3]);
+}
+
+#[test]
+fn quantise_black_white() {
+    let image = row(&[(0x10, 0xFF), (0xF0, 0xFF), (0, 0)]);
+    let colors = quantise::<Bpp1>(&image, &ToneCurve::LINEAR, Dithering::None, Some(5)).unwrap();
+    assert_eq!(colors, [0, 15, 5]);
+}
+
+#[test]
+fn error_diffusion_is_limited_to_screen_width() {
+    let image = GrayAlphaImage::new(961, 1);
+    let curve = ToneCurve::LINEAR;
+    for dithering in [Dithering::FloydSteinberg, Dithering::Atkinson] {
+        assert!(quantise::<Bpp4>(&image, &curve, dithering, None).is_err());
+    }
+    for dithering in [Dithering::None, Dithering::Bayer] {
+        assert!(quantise::<Bpp4>(&image, &curve, dithering, None).is_ok());
+    }
+}
+
+#[test]
+fn grayscale_blends_or_marks_transparent_pixels() {
+    let mut image = RgbaImage::new(3, 1);
+    image.put_pixel(0, 0, Rgba([0, 0, 0, 0xFF]));
+    image.put_pixel(1, 0, Rgba([0, 0, 0, 0]));
+    image.put_pixel(2, 0, Rgba([0, 0, 0, 0x7F]));
+    let blended: Vec<_> = grayscale(&image, 200, false).pixels().map(|p| p.0).collect();
+    assert_eq!(blended, [[0, 0xFF], [200, 0xFF], [100, 0xFF]]);
+    let marked: Vec<_> = grayscale(&image, 200, true).pixels().map(|p| p.0).collect();
+    assert_eq!(marked, [[0, 0xFF], [200, 0], [200, 0]]);
+}
+
+#[test]
+fn pack_stores_left_pixel_in_lower_nibble() {
+    assert_eq!(pack(&[1, 2, 3, 4, 5], 5), [0x21, 0x43, 0x05]);
+    // odd rows are padded separately
+    assert_eq!(pack(&[1, 2, 3, 4, 5, 6], 3), [0x21, 0x03, 0x54, 0x06]);
+    assert_eq!(pack(&[0xF, 0], 2), [0x0F]);
+}
+
+#[test]
+fn converted_image_round_trip() {
+    let (width, height) = (7u16, 5u16);
+    let mut image = GrayAlphaImage::new(width as u32, height as u32);
+    for (i, pixel) in image.pixels_mut().enumerate() {
+        let alpha = if i % 11 == 0 { 0 } else { 0xFF };
+        *pixel = LumaA([(i * 37) as u8, alpha]);
+    }
+    let key = Some(9);
+    let colors = quantise::<Bpp4>(&image, &ToneCurve::LINEAR, Dithering::Atkinson, key).unwrap();
+    let data = pack(&colors, width as usize);
+    for compress in [false, true] {
+        let mut out = vec![0; Image4bpp::HEADER_SIZE + 2 * data.len()];
+        let len = Image4bpp::encode(width, height, &data, key, compress, &mut out).unwrap();
+        let decoded = Image4bpp::new(&out[..len]).unwrap();
+        assert_eq!((decoded.width(), decoded.height()), (width, height));
+        assert_eq!(decoded.transparent(), key);
+        let mut pixels = vec![0; data.len()];
+        match compress {
+            true => codec::decode(decoded.data(), &mut pixels).unwrap(),
+            false => pixels.copy_from_slice(decoded.data()),
+        }
+        for (i, &color) in colors.iter().enumerate() {
+            let (x, y) = (i % width as usize, i / width as usize);
+            let byte = pixels[y * decoded.stride() + x / 2];
+            assert_eq!(byte >> (4 * (x % 2)) & 0x0F, color, "pixel {x}, {y}");
+            assert_eq!(color == 9, i % 11 == 0);
+        }
+    }
+}